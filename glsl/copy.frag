@@ -1,8 +1,68 @@
 #version 330 core
+out vec4 outColor;
 
 uniform sampler2D state;
 uniform vec2 scale;
 
+// Color of every state in a 256x1 texture
+uniform sampler2D palette;
+uniform bool hex;
+
+// How the edges are joined, see Topology in rule.rs
+const int TORUS = 0;
+const int PLANE = 1;
+const int KLEIN_BOTTLE = 2;
+const int CROSS_SURFACE = 3;
+uniform int topology;
+
+// GLSL leaves / and % undefined for negative operands
+int floorDiv(int v, int n) {
+    return v >= 0 ? v / n : -((-v - 1) / n) - 1;
+}
+
+// State of a cell that may lie past the edges, the same way the gol shader
+// reads it
+int get(ivec2 pos) {
+    ivec2 size = textureSize(state, 0);
+    ivec2 tile = ivec2(floorDiv(pos.x, size.x), floorDiv(pos.y, size.y));
+
+    if (topology == PLANE && tile != ivec2(0, 0)) {
+        return 0;
+    }
+
+    pos -= tile * size;
+    if ((topology == KLEIN_BOTTLE || topology == CROSS_SURFACE) && (tile.y & 1) != 0) {
+        pos.x = size.x - 1 - pos.x;
+    }
+    if (topology == CROSS_SURFACE && (tile.x & 1) != 0) {
+        pos.y = size.y - 1 - pos.y;
+    }
+
+    return int(texelFetch(state, pos, 0).r * 255.0 + 0.5);
+}
+
+// Hexagonal rules have cell (x, y) centered at (x + y / 2, y), so that its
+// six neighbors surround it. Round to the closest center in cube coordinates
+ivec2 hexCell(vec2 pos) {
+    float r = pos.y - 0.5;
+    float q = pos.x - 0.5 - r * 0.5;
+    vec3 cube = vec3(q, r, -q - r);
+    vec3 rounded = round(cube);
+    vec3 diff = abs(rounded - cube);
+
+    if (diff.x > diff.y && diff.x > diff.z) {
+        rounded.x = -rounded.y - rounded.z;
+    } else if (diff.y > diff.z) {
+        rounded.y = -rounded.x - rounded.z;
+    }
+
+    return ivec2(rounded.xy);
+}
+
 void main() {
-    gl_FragColor = texture2D(state, gl_FragCoord.xy / scale);
+    ivec2 size = textureSize(state, 0);
+    vec2 pos = gl_FragCoord.xy / scale * vec2(size);
+    ivec2 cell = hex ? hexCell(pos) : ivec2(pos);
+
+    outColor = texelFetch(palette, ivec2(get(cell), 0), 0);
 }
@@ -0,0 +1,23 @@
+#version 330 core
+out vec4 outColor;
+
+uniform sampler2D front;
+uniform sampler2D back;
+uniform int tileSize;
+
+// One texel per tile of the board, set when any cell in it changed on the
+// last tick
+void main() {
+    ivec2 start = ivec2(gl_FragCoord.xy) * tileSize;
+    ivec2 end = min(start + tileSize, textureSize(front, 0));
+
+    bool changed = false;
+    for (int y = start.y; y < end.y && !changed; y++) {
+        for (int x = start.x; x < end.x && !changed; x++) {
+            ivec2 pos = ivec2(x, y);
+            changed = texelFetch(front, pos, 0).r != texelFetch(back, pos, 0).r;
+        }
+    }
+
+    outColor = vec4(changed ? 1.0 : 0.0, 0.0, 0.0, 1.0);
+}
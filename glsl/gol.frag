@@ -2,29 +2,80 @@
 out vec4 outColor;
 
 uniform sampler2D state;
-uniform vec2 scale;
 
-int get(vec2 offset) {
-    return int(texture2D(state, (gl_FragCoord.xy + offset) / scale).r);
+// The rule, one bit per neighbor configuration, see Transitions in rule.rs.
+// Neighbors outside of the rule's neighborhood never change the lookup
+uniform uint birth[8];
+uniform uint survive[8];
+uniform int states;
+
+// How the edges are joined, see Topology in rule.rs
+const int TORUS = 0;
+const int PLANE = 1;
+const int KLEIN_BOTTLE = 2;
+const int CROSS_SURFACE = 3;
+uniform int topology;
+
+// GLSL leaves / and % undefined for negative operands
+int floorDiv(int v, int n) {
+    return v >= 0 ? v / n : -((-v - 1) / n) - 1;
+}
+
+int get(ivec2 offset) {
+    ivec2 size = textureSize(state, 0);
+    ivec2 pos = ivec2(gl_FragCoord.xy) + offset;
+    ivec2 tile = ivec2(floorDiv(pos.x, size.x), floorDiv(pos.y, size.y));
+
+    if (topology == PLANE && tile != ivec2(0, 0)) {
+        return 0;
+    }
+
+    pos -= tile * size;
+    // Crossing a twisted edge mirrors the position along it
+    if ((topology == KLEIN_BOTTLE || topology == CROSS_SURFACE) && (tile.y & 1) != 0) {
+        pos.x = size.x - 1 - pos.x;
+    }
+    if (topology == CROSS_SURFACE && (tile.x & 1) != 0) {
+        pos.y = size.y - 1 - pos.y;
+    }
+
+    return int(texelFetch(state, pos, 0).r * 255.0 + 0.5);
+}
+
+// Only fully alive cells count as neighbors, dying cells do not
+uint alive(ivec2 offset) {
+    return get(offset) == 1 ? 1u : 0u;
+}
+
+bool lookup(uint table[8], uint config) {
+    return ((table[config >> 5] >> (config & 31u)) & 1u) == 1u;
 }
 
 void main() {
-    int sum =
-        get(vec2(-1.0, -1.0)) +
-        get(vec2(-1.0,  0.0)) +
-        get(vec2(-1.0,  1.0)) +
-        get(vec2( 0.0, -1.0)) +
-        get(vec2( 0.0,  1.0)) +
-        get(vec2( 1.0, -1.0)) +
-        get(vec2( 1.0,  0.0)) +
-        get(vec2( 1.0,  1.0));
-
-    if (sum == 3) {
-        gl_FragColor = vec4(1.0, 1.0, 1.0, 1.0);
-    } else if (sum == 2) {
-        float current = float(get(vec2(0.0, 0.0)));
-        gl_FragColor = vec4(current, current, current, 1.0);
-    } else {
-        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+    // One bit per neighbor, NW is the highest and SE the lowest
+    uint config =
+        alive(ivec2(-1,  1)) << 7 |
+        alive(ivec2( 0,  1)) << 6 |
+        alive(ivec2( 1,  1)) << 5 |
+        alive(ivec2(-1,  0)) << 4 |
+        alive(ivec2( 1,  0)) << 3 |
+        alive(ivec2(-1, -1)) << 2 |
+        alive(ivec2( 0, -1)) << 1 |
+        alive(ivec2( 1, -1));
+
+    int current = get(ivec2(0, 0));
+
+    int next = 0;
+
+    if (current == 0) {
+        if (lookup(birth, config)) { next = 1; }
+    } else if (current == 1) {
+        // Cells that fail to survive start dying
+        if (lookup(survive, config)) { next = 1; } else { next = 2 % states; }
+    } else if (current + 1 < states) {
+        // States left over from a rule with more of them die out right away
+        next = current + 1;
     }
-}
\ No newline at end of file
+
+    outColor = vec4(float(next) / 255.0, 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,103 @@
+#version 330 core
+out vec4 outColor;
+
+uniform sampler2D state;
+uniform isampler2D prefix;
+
+// The rule, halfWidth has room for 2 * MAX_RANGE + 1 rows
+uniform int range;
+uniform int halfWidth[101];
+uniform bool middle;
+uniform ivec2 birth;
+uniform ivec2 survive;
+uniform int states;
+
+int get(ivec2 pos) {
+    return int(texelFetch(state, pos, 0).r * 255.0 + 0.5);
+}
+
+// How the edges are joined, see Topology in rule.rs
+const int TORUS = 0;
+const int PLANE = 1;
+const int KLEIN_BOTTLE = 2;
+const int CROSS_SURFACE = 3;
+uniform int topology;
+
+// GLSL leaves / and % undefined for negative operands
+int floorDiv(int v, int n) {
+    return v >= 0 ? v / n : -((-v - 1) / n) - 1;
+}
+
+// Live cells in columns lo to hi of row y, all on the board
+int span(int y, int lo, int hi) {
+    int upToHi = texelFetch(prefix, ivec2(hi, y), 0).r;
+    int beforeLo = lo > 0 ? texelFetch(prefix, ivec2(lo - 1, y), 0).r : 0;
+    return upToHi - beforeLo;
+}
+
+// Live cells in columns x - w to x + w of row y, which may reach past the
+// edges. Each copy of the board the row passes through adds one span
+int rowSum(int y, int x, int w, ivec2 size) {
+    int tileY = floorDiv(y, size.y);
+    if (topology == PLANE && tileY != 0) {
+        return 0;
+    }
+
+    int sum = 0;
+    for (int tileX = floorDiv(x - w, size.x); tileX <= floorDiv(x + w, size.x); tileX++) {
+        if (topology == PLANE && tileX != 0) {
+            continue;
+        }
+
+        int row = y - tileY * size.y;
+        int lo = max(x - w - tileX * size.x, 0);
+        int hi = min(x + w - tileX * size.x, size.x - 1);
+
+        // Crossing a twisted edge mirrors the position along it
+        if ((topology == KLEIN_BOTTLE || topology == CROSS_SURFACE) && (tileY & 1) != 0) {
+            int mirrored = size.x - 1 - hi;
+            hi = size.x - 1 - lo;
+            lo = mirrored;
+        }
+        if (topology == CROSS_SURFACE && (tileX & 1) != 0) {
+            row = size.y - 1 - row;
+        }
+
+        sum += span(row, lo, hi);
+    }
+
+    return sum;
+}
+
+bool within(int sum, ivec2 bounds) {
+    return sum >= bounds.x && sum <= bounds.y;
+}
+
+void main() {
+    ivec2 size = textureSize(state, 0);
+    ivec2 pos = ivec2(gl_FragCoord.xy);
+
+    int sum = 0;
+    for (int dy = -range; dy <= range; dy++) {
+        sum += rowSum(pos.y + dy, pos.x, halfWidth[dy + range], size);
+    }
+
+    int current = get(pos);
+    if (!middle && current == 1) {
+        sum -= 1;
+    }
+
+    int next = 0;
+
+    if (current == 0) {
+        if (within(sum, birth)) { next = 1; }
+    } else if (current == 1) {
+        // Cells that fail to survive start dying
+        if (within(sum, survive)) { next = 1; } else { next = 2 % states; }
+    } else if (current + 1 < states) {
+        // States left over from a rule with more of them die out right away
+        next = current + 1;
+    }
+
+    outColor = vec4(float(next) / 255.0, 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,9 @@
+#version 330 core
+out ivec4 outColor;
+
+uniform sampler2D state;
+
+void main() {
+    int current = int(texelFetch(state, ivec2(gl_FragCoord.xy), 0).r * 255.0 + 0.5);
+    outColor = ivec4(current == 1 ? 1 : 0, 0, 0, 0);
+}
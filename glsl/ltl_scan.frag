@@ -0,0 +1,16 @@
+#version 330 core
+out ivec4 outColor;
+
+uniform isampler2D prefix;
+uniform int step;
+
+// One step of a prefix sum along each row, after log2(width) steps every
+// texel holds the number of live cells at or to the left of it
+void main() {
+    ivec2 pos = ivec2(gl_FragCoord.xy);
+    int sum = texelFetch(prefix, pos, 0).r;
+    if (pos.x >= step) {
+        sum += texelFetch(prefix, pos - ivec2(step, 0), 0).r;
+    }
+    outColor = ivec4(sum, 0, 0, 0);
+}
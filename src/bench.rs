@@ -0,0 +1,294 @@
+use std::str::FromStr;
+
+use glfw::{Glfw, Window};
+use gumdrop::Options;
+
+use crate::game_of_life::BasicGoL;
+use crate::rule::Rule;
+use crate::simulator::{Engine, Simulator};
+use crate::{offscreen_context, GpuGoL, Size};
+
+const ENGINES: [Engine; 5] = [
+    Engine::Basic,
+    Engine::Packed,
+    Engine::Threaded,
+    Engine::HashLife,
+    Engine::Gpu,
+];
+
+#[derive(Debug, Options)]
+pub struct BenchOptions {
+    #[options(help = "print help message")]
+    help: bool,
+
+    #[options(
+        help = "Engine to time: basic, packed, threaded, hashlife or gpu. Can be given more than once, defaults to all of them",
+        parse(try_from_str),
+        no_short
+    )]
+    engine: Vec<Engine>,
+
+    #[options(
+        help = "Board size",
+        default = "512x512",
+        parse(try_from_str),
+        no_short
+    )]
+    size: Size,
+
+    #[options(help = "Generations every sample runs for", default = "100", no_short)]
+    gens: u64,
+
+    #[options(
+        help = "Rule to run",
+        default = "B3/S23",
+        parse(try_from_str),
+        no_short
+    )]
+    rule: Rule,
+
+    #[options(help = "Untimed runs before the samples", default = "1", no_short)]
+    warmup: usize,
+
+    #[options(help = "Timed runs", default = "10", no_short)]
+    samples: usize,
+
+    #[options(help = "Chance of a cell starting alive", default = "0.4", no_short)]
+    density: f64,
+
+    #[options(help = "Seed of the random board", default = "1", no_short)]
+    seed: u64,
+
+    #[options(
+        help = "Thread counts to time the threaded engine with, like 1,2,4. Defaults to 1, 2, 4 and so on up to one per core",
+        parse(try_from_str),
+        no_short
+    )]
+    threads: Option<Threads>,
+
+    #[options(help = "Print the results as JSON instead of a table", no_short)]
+    json: bool,
+}
+
+#[derive(Debug)]
+struct Threads(Vec<usize>);
+
+impl FromStr for Threads {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(',')
+            .map(|n| match n.trim().parse() {
+                Ok(0) | Err(_) => Err("Threads must be positive numbers separated by commas"),
+                Ok(n) => Ok(n),
+            })
+            .collect::<Result<Vec<usize>, _>>()
+            .map(Threads)
+    }
+}
+
+// An engine to time, the threaded one once for every thread count
+#[derive(Clone, Copy)]
+struct Run {
+    engine: Engine,
+    threads: Option<usize>,
+}
+
+impl Run {
+    fn name(&self) -> String {
+        match self.threads {
+            Some(threads) => format!("{} {}", self.engine, threads),
+            None => self.engine.to_string(),
+        }
+    }
+}
+
+// Timings of one engine, in milliseconds for all the generations of a sample
+struct Timings {
+    median: f64,
+    p95: f64,
+    min: f64,
+}
+
+// Times every engine running the same random board. Every sample starts over
+// from that board so that they all do the same work
+pub fn bench(opts: BenchOptions) {
+    if opts.samples == 0 {
+        println!("Need at least one sample");
+        return;
+    }
+
+    let engines = if opts.engine.is_empty() {
+        ENGINES.to_vec()
+    } else {
+        opts.engine.clone()
+    };
+
+    let threads = match &opts.threads {
+        Some(Threads(counts)) => counts.clone(),
+        None => {
+            let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+            let mut counts = std::iter::successors(Some(1), |t| Some(t * 2))
+                .take_while(|&t| t < cores)
+                .collect::<Vec<usize>>();
+            counts.push(cores);
+            counts
+        }
+    };
+    let runs = engines
+        .iter()
+        .flat_map(|&engine| match engine {
+            Engine::Threaded => threads
+                .iter()
+                .map(|&threads| Run {
+                    engine,
+                    threads: Some(threads),
+                })
+                .collect(),
+            _ => vec![Run {
+                engine,
+                threads: None,
+            }],
+        })
+        .collect::<Vec<Run>>();
+
+    // Created for the first GPU run, has to outlive the GpuGoL
+    let mut context = None;
+
+    let results = runs
+        .iter()
+        .map(|&run| {
+            let timings =
+                simulator(run, &opts, &mut context).map(|mut sim| time(sim.as_mut(), &opts));
+            (run, timings)
+        })
+        .collect::<Vec<_>>();
+
+    if opts.json {
+        print_json(&opts, &results);
+    } else {
+        print_table(&opts, &results);
+    }
+}
+
+fn simulator(
+    run: Run,
+    opts: &BenchOptions,
+    context: &mut Option<(Glfw, Window)>,
+) -> Result<Box<dyn Simulator>, String> {
+    let Size { width, height } = opts.size;
+
+    if let Some(threads) = run.threads {
+        let mut basic = BasicGoL::new(width, height, &opts.rule);
+        basic.set_threads(threads);
+        return Ok(Box::new(basic));
+    }
+
+    match run.engine {
+        Engine::Gpu => {
+            if context.is_none() {
+                *context = Some(offscreen_context(width as u32, height as u32)?);
+            }
+            Ok(Box::new(GpuGoL::new(
+                width as u32,
+                height as u32,
+                &opts.rule,
+            )))
+        }
+        engine => Ok(engine.new_cpu(width, height, &opts.rule)?),
+    }
+}
+
+fn time(sim: &mut dyn Simulator, opts: &BenchOptions) -> Timings {
+    let mut samples = (0..opts.warmup + opts.samples)
+        .map(|_| {
+            sim.randomize(opts.density, opts.seed);
+            sim.bench(opts.gens)
+        })
+        .skip(opts.warmup)
+        .collect::<Vec<f64>>();
+    samples.sort_by(f64::total_cmp);
+
+    let n = samples.len();
+    Timings {
+        median: if n % 2 == 0 {
+            (samples[n / 2 - 1] + samples[n / 2]) / 2.0
+        } else {
+            samples[n / 2]
+        },
+        // Nearest rank
+        p95: samples[((n as f64 * 0.95).ceil() as usize).max(1) - 1],
+        min: samples[0],
+    }
+}
+
+fn print_table(opts: &BenchOptions, results: &[(Run, Result<Timings, String>)]) {
+    println!(
+        "{} on {}x{}, {} generations, median of {} samples",
+        opts.rule, opts.size.width, opts.size.height, opts.gens, opts.samples
+    );
+    println!(
+        "{:<12} {:>12} {:>12} {:>12} {:>14} {:>10}",
+        "engine", "median ms", "p95 ms", "min ms", "ms / gen", "speedup"
+    );
+
+    // Threaded runs are compared with the first thread count
+    let mut single = None;
+
+    for (run, timings) in results {
+        match timings {
+            Ok(t) => {
+                let speedup = match run.threads {
+                    Some(_) => format!("{:.2}x", *single.get_or_insert(t.median) / t.median),
+                    None => String::new(),
+                };
+                println!(
+                    "{:<12} {:>12.3} {:>12.3} {:>12.3} {:>14.6} {:>10}",
+                    run.name(),
+                    t.median,
+                    t.p95,
+                    t.min,
+                    t.median / opts.gens as f64,
+                    speedup
+                )
+            }
+            Err(e) => println!("{:<12} skipped, {}", run.name(), e),
+        }
+    }
+}
+
+fn print_json(opts: &BenchOptions, results: &[(Run, Result<Timings, String>)]) {
+    let results = results
+        .iter()
+        .map(|(run, timings)| {
+            let engine = match run.threads {
+                Some(threads) => format!("\"engine\":\"{}\",\"threads\":{}", run.engine, threads),
+                None => format!("\"engine\":\"{}\"", run.engine),
+            };
+            (engine, timings)
+        })
+        .map(|(engine, timings)| match timings {
+            Ok(t) => format!(
+                "{{{},\"median_ms\":{:.6},\"p95_ms\":{:.6},\"min_ms\":{:.6}}}",
+                engine, t.median, t.p95, t.min
+            ),
+            Err(e) => format!(
+                "{{{},\"error\":\"{}\"}}",
+                engine,
+                e.replace('\\', "\\\\").replace('"', "\\\"")
+            ),
+        })
+        .collect::<Vec<String>>();
+
+    println!(
+        "{{\"rule\":\"{}\",\"width\":{},\"height\":{},\"gens\":{},\"warmup\":{},\"samples\":{},\"results\":[{}]}}",
+        opts.rule,
+        opts.size.width,
+        opts.size.height,
+        opts.gens,
+        opts.warmup,
+        opts.samples,
+        results.join(",")
+    );
+}
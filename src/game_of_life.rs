@@ -1,21 +1,139 @@
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::rule::{Rule, RuleKind, NEIGHBORS};
+use crate::simulator::{Region, Simulator};
 
 pub struct BasicGoL {
     width: usize,
     height: usize,
     big_width: usize,
     big_height: usize,
+    // How many rings of border cells surround the grid
+    border: usize,
+    rule: Rule,
     prev: Vec<u8>,
     next: Vec<u8>,
+    tiles: Tiles,
+    // Used when ticking through Simulator
+    threads: usize,
+}
+
+// Square tiles of the grid with whether they changed on the last tick and on
+// the one before it. Tiles where nothing nearby changed on either are
+// skipped, the buffer about to be written then already holds their cells
+pub struct Tiles {
+    pub size: usize,
+    pub columns: usize,
+    pub rows: usize,
+    // How many tiles away a change can have an effect on the next tick
+    reach: usize,
+    changed: Vec<AtomicBool>,
+    changed_before: Vec<bool>,
+}
+
+impl Tiles {
+    pub fn new(size: usize, width: usize, height: usize, range: usize) -> Self {
+        let columns = width.div_ceil(size);
+        let rows = height.div_ceil(size);
+
+        Tiles {
+            size,
+            columns,
+            rows,
+            reach: range.div_ceil(size),
+            // Even an empty board changes under rules with B0
+            changed: (0..columns * rows).map(|_| AtomicBool::new(true)).collect(),
+            changed_before: vec![true; columns * rows],
+        }
+    }
+
+    pub fn set_range(&mut self, range: usize) {
+        self.reach = range.div_ceil(self.size);
+    }
+
+    pub fn set_changed(&mut self, tile: usize) {
+        *self.changed[tile].get_mut() = true;
+    }
+
+    fn mark(&self, row: usize, col: usize) {
+        self.changed[row / self.size * self.columns + col / self.size]
+            .store(true, Ordering::Relaxed);
+    }
+
+    pub fn mark_all(&mut self) {
+        for changed in &mut self.changed {
+            *changed.get_mut() = true;
+        }
+    }
+
+    // Which tiles the next tick has to compute
+    fn active(&self) -> Vec<bool> {
+        let recent = self
+            .changed
+            .iter()
+            .zip(&self.changed_before)
+            .map(|(changed, &before)| changed.load(Ordering::Relaxed) || before)
+            .collect::<Vec<bool>>();
+
+        active_tiles(&recent, self.columns, self.rows, self.reach)
+    }
+
+    // Moves on to the next tick and returns which tiles have to be computed
+    pub fn next_tick(&mut self) -> Vec<bool> {
+        let active = self.active();
+        for (changed, before) in self.changed.iter_mut().zip(&mut self.changed_before) {
+            *before = std::mem::replace(changed.get_mut(), false);
+        }
+        active
+    }
+}
+
+// Tiles within `reach` tiles of one that changed recently. Depending on the
+// topology, tiles within reach of an edge can have neighbors anywhere along
+// the edges, so those all become active together
+fn active_tiles(recent: &[bool], columns: usize, rows: usize, reach: usize) -> Vec<bool> {
+    let near_edge =
+        |x: usize, y: usize| x < reach || y < reach || x + reach >= columns || y + reach >= rows;
+    let mut active = vec![false; recent.len()];
+    let mut edge_changed = false;
+
+    for y in 0..rows {
+        for x in 0..columns {
+            if !recent[y * columns + x] {
+                continue;
+            }
+
+            edge_changed |= near_edge(x, y);
+            for ny in y.saturating_sub(reach)..(y + reach + 1).min(rows) {
+                for nx in x.saturating_sub(reach)..(x + reach + 1).min(columns) {
+                    active[ny * columns + nx] = true;
+                }
+            }
+        }
+    }
+
+    if edge_changed {
+        for y in 0..rows {
+            for x in 0..columns {
+                if near_edge(x, y) {
+                    active[y * columns + x] = true;
+                }
+            }
+        }
+    }
+
+    active
 }
 
 impl BasicGoL {
-    pub fn new(width: usize, height: usize) -> Self {
-        // Optimization #1 add a border of 0's around the whole grid so that you can always access
+    pub fn new(width: usize, height: usize, rule: &Rule) -> Self {
+        // Optimization #1 add a border around the whole grid so that you can always access
         // the neighbors of the elements within that grid instead of needing to check if we are
-        // accessing elements out of the grid
-        let big_width = width + 2;
-        let big_height = height + 2;
+        // accessing elements out of the grid. Before every tick the border is filled in with
+        // the cells the topology says lie past the edges
+        let border = rule.range() as usize;
+        let big_width = width + 2 * border;
+        let big_height = height + 2 * border;
         let n = big_width * big_height;
 
         Self {
@@ -23,110 +141,641 @@ impl BasicGoL {
             height,
             big_width,
             big_height,
+            border,
+            rule: *rule,
             prev: vec![0u8; n],
             next: vec![0u8; n],
+            tiles: Tiles::new(32, width, height, border),
+            threads: 1,
         }
     }
 
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads;
+    }
+
+    // Rows from the top, like the rows of a pattern file. The GPU textures
+    // store them from the bottom
     pub fn iter(&self) -> impl Iterator<Item = impl Iterator<Item = u8> + '_> + '_ {
-        (1..(self.height + 1)).map(move |r| {
-            let start = r * self.big_width + 1;
+        (self.border..(self.height + self.border)).map(move |r| {
+            let start = r * self.big_width + self.border;
             self.prev[start..start + self.width].iter().copied()
         })
     }
 
+    pub fn set(&mut self, row: usize, col: usize, val: u8) {
+        self.prev[(row + self.border) * self.big_width + col + self.border] = val;
+        self.tiles.mark(row, col);
+    }
+
     fn get_internal(&self, row: usize, col: usize) -> u8 {
         unsafe { *self.prev.get_unchecked(row * self.big_width + col) }
     }
 
-    fn set_internal(&mut self, row: usize, col: usize, val: u8) {
-        unsafe {
-            *self.next.get_unchecked_mut(row * self.big_width + col) = val;
+    fn fill_border(&mut self) {
+        let border = self.border as i64;
+        let (width, height) = (self.width as i64, self.height as i64);
+
+        let rows = self.border..(self.height + self.border);
+        let cols = self.border..(self.width + self.border);
+
+        for r in 0..self.big_height {
+            for c in 0..self.big_width {
+                if rows.contains(&r) && cols.contains(&c) {
+                    continue;
+                }
+
+                let x = c as i64 - border;
+                let y = r as i64 - border;
+                let val = match self.rule.topology.wrap(x, y, width, height) {
+                    Some((x, y)) => {
+                        self.get_internal(y as usize + self.border, x as usize + self.border)
+                    }
+                    None => 0,
+                };
+
+                self.prev[r * self.big_width + c] = val;
+            }
         }
     }
 
-    fn sum_neighbors(&self, row: usize, col: usize) -> u8 {
-        0 + self.get_internal(row - 1, col)
-            + self.get_internal(row + 1, col)
-            + self.get_internal(row, col - 1)
-            + self.get_internal(row, col + 1)
-            + self.get_internal(row - 1, col - 1)
-            + self.get_internal(row - 1, col + 1)
-            + self.get_internal(row + 1, col - 1)
-            + self.get_internal(row + 1, col + 1)
+    // One bit per neighbor that is alive, in the order of rule::NEIGHBORS.
+    // Dying cells do not count
+    fn neighbor_config(&self, row: usize, col: usize) -> u8 {
+        NEIGHBORS.iter().fold(0, |config, &(x, y)| {
+            let cell = self.get_internal((row as i32 + y) as usize, (col as i32 + x) as usize);
+            config << 1 | (cell == 1) as u8
+        })
     }
 
     fn swap_buffers(&mut self) {
         std::mem::swap(&mut self.prev, &mut self.next);
     }
 
-    pub fn fill_with_gliders(&mut self) {
-        for r in (1..(self.height + 1)).step_by(5) {
-            for c in (1..(self.width + 1)).step_by(5) {
-                self.set_internal(r + 0, c + 1, 1);
+    // Computes horizontal bands of the grid in parallel, one per thread. The
+    // result is the same for any number of threads
+    pub fn tick_threaded(&mut self, threads: usize) {
+        self.fill_border();
+        let active = self.tiles.next_tick();
+
+        // Live cells to the left of every column, one row after the other, so
+        // that Larger than Life rules can count any run of a row with a
+        // subtraction
+        let stride = self.big_width + 1;
+        let mut prefix = Vec::new();
+        if let RuleKind::LargerThanLife(_) = self.rule.kind {
+            prefix = vec![0u32; self.big_height * stride];
+            in_bands(&mut prefix, stride, threads, |first, rows| {
+                for (i, row) in rows.chunks_mut(stride).enumerate() {
+                    for c in 0..self.big_width {
+                        row[c + 1] = row[c] + (self.get_internal(first + i, c) == 1) as u32;
+                    }
+                }
+            });
+        }
+
+        // Only the rows of the grid itself, the border is filled in next tick
+        let mut next = std::mem::take(&mut self.next);
+        let start = self.border * self.big_width;
+        let end = (self.height + self.border) * self.big_width;
+
+        in_bands(
+            &mut next[start..end],
+            self.big_width,
+            threads,
+            |first, rows| {
+                for (i, row) in rows.chunks_mut(self.big_width).enumerate() {
+                    self.tick_row(first + i + self.border, row, &prefix, &active);
+                }
+            },
+        );
+
+        self.next = next;
+        self.swap_buffers();
+    }
 
-                self.set_internal(r + 1, c + 2, 1);
+    // Computes the active tiles of row r into out
+    fn tick_row(&self, r: usize, out: &mut [u8], prefix: &[u32], active: &[bool]) {
+        let tiles = &self.tiles;
+        let tile_row = (r - self.border) / tiles.size;
 
-                self.set_internal(r + 2, c + 0, 1);
-                self.set_internal(r + 2, c + 1, 1);
-                self.set_internal(r + 2, c + 2, 1);
+        for tile in 0..tiles.columns {
+            let i = tile_row * tiles.columns + tile;
+            if !active[i] {
+                continue;
+            }
+
+            let start = self.border + tile * tiles.size;
+            let end = self.border + ((tile + 1) * tiles.size).min(self.width);
+
+            let mut changed = false;
+            for (c, cell) in out.iter_mut().enumerate().take(end).skip(start) {
+                let val = self.next_cell(r, c, prefix);
+                changed |= val != self.get_internal(r, c);
+                *cell = val;
+            }
+
+            if changed {
+                tiles.changed[i].store(true, Ordering::Relaxed);
             }
         }
+    }
 
-        self.swap_buffers();
+    fn next_cell(&self, r: usize, c: usize, prefix: &[u32]) -> u8 {
+        let current = self.get_internal(r, c);
+
+        match self.rule.kind {
+            RuleKind::Life { birth, survive, .. } => {
+                let config = self.neighbor_config(r, c);
+                self.rule
+                    .next_state(current, birth.contains(config), survive.contains(config))
+            }
+            RuleKind::LargerThanLife(ltl) => {
+                let stride = self.big_width + 1;
+                let range = ltl.range as i32;
+
+                let mut sum = 0;
+                for dy in -range..=range {
+                    let w = ltl.shape.half_width(ltl.range, dy).unwrap() as usize;
+                    let row = (r as i32 + dy) as usize * stride;
+                    sum += prefix[row + c + w + 1] - prefix[row + c - w];
+                }
+                if !ltl.middle && current == 1 {
+                    sum -= 1;
+                }
+
+                self.rule.next_state(
+                    current,
+                    sum >= ltl.birth.0 && sum <= ltl.birth.1,
+                    sum >= ltl.survive.0 && sum <= ltl.survive.1,
+                )
+            }
+        }
     }
+}
 
-    pub fn tick(&mut self) {
-        for r in 1..(self.height + 1) {
-            for c in 1..(self.width + 1) {
-                let sum = self.sum_neighbors(r, c);
+impl Simulator for BasicGoL {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn set_rule(&mut self, rule: &Rule) -> Result<(), &'static str> {
+        let cells = self.read_state();
+        let threads = self.threads;
+
+        // The border has to be as wide as the new rule's range
+        *self = BasicGoL::new(self.width, self.height, rule);
+        self.threads = threads;
+        self.set_cells(Region::new(0, 0, self.width, self.height), &cells);
+        Ok(())
+    }
+
+    fn tick(&mut self, generations: u64) {
+        for _ in 0..generations {
+            self.tick_threaded(self.threads);
+        }
+    }
+
+    fn set_cells(&mut self, region: Region, data: &[u8]) {
+        for ((x, y), &cell) in region.cells().zip(data) {
+            self.set(y, x, cell);
+        }
+    }
+
+    fn read_state(&self) -> Vec<u8> {
+        self.iter().flatten().collect()
+    }
+}
+
+// Calls f on bands of consecutive rows in parallel, one thread per band. f
+// gets the index of the first row in its band
+fn in_bands<T: Send>(
+    rows: &mut [T],
+    row_len: usize,
+    threads: usize,
+    f: impl Fn(usize, &mut [T]) + Sync,
+) {
+    if threads <= 1 {
+        f(0, rows);
+        return;
+    }
+
+    let band = (rows.len() / row_len).div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        for (i, chunk) in rows.chunks_mut(band * row_len).enumerate() {
+            let f = &f;
+            scope.spawn(move || f(i * band, chunk));
+        }
+    });
+}
+
+// Same as BasicGoL, but with 64 cells packed into every u64 so that the
+// neighbors of all of them are counted at once with bit sliced adders. Only
+// runs two state rules that depend on nothing but the number of neighbors
+pub struct PackedGoL {
+    width: usize,
+    height: usize,
+    // Words per row. Like in BasicGoL every row has a border cell on either
+    // side, and there is a border row above and below the grid
+    words: usize,
+    big_height: usize,
+    rule: Rule,
+    // Offsets of the neighbors that are counted
+    neighbors: Vec<(i32, i32)>,
+    // One bit per neighbor count
+    birth: u16,
+    survive: u16,
+    // Bits of the grid itself in every word of a row
+    inner: Vec<u64>,
+    prev: Vec<u64>,
+    next: Vec<u64>,
+}
+
+impl PackedGoL {
+    pub fn new(width: usize, height: usize, rule: &Rule) -> Result<Self, &'static str> {
+        let RuleKind::Life {
+            birth,
+            survive,
+            neighborhood,
+        } = rule.kind
+        else {
+            return Err("The packed engine can't run Larger than Life rules");
+        };
+        if rule.states != 2 {
+            return Err("The packed engine can't run Generations rules");
+        }
+
+        let mask = neighborhood.mask();
+        let (Some(birth), Some(survive)) = (birth.counts(mask), survive.counts(mask)) else {
+            return Err("The packed engine can't run non-totalistic rules");
+        };
 
-                let val = if self.get_internal(r, c) == 1 {
-                    sum == 2 || sum == 3
-                } else {
-                    sum == 3
+        let neighbors = NEIGHBORS
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| mask & (0x80 >> i) != 0)
+            .map(|(_, &n)| n)
+            .collect();
+
+        let words = (width + 2).div_ceil(64);
+        let big_height = height + 2;
+        let inner = (0..words)
+            .map(|k| {
+                (0..64)
+                    .filter(|i| (1..=width).contains(&(k * 64 + i)))
+                    .fold(0, |word, i| word | 1 << i)
+            })
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            words,
+            big_height,
+            rule: *rule,
+            neighbors,
+            birth,
+            survive,
+            inner,
+            prev: vec![0; words * big_height],
+            next: vec![0; words * big_height],
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = impl Iterator<Item = u8> + '_> + '_ {
+        (0..self.height).map(move |r| (0..self.width).map(move |c| self.get(r, c)))
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> u8 {
+        self.get_internal(row + 1, col + 1)
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, val: u8) {
+        self.set_internal(row + 1, col + 1, val);
+    }
+
+    fn get_internal(&self, row: usize, col: usize) -> u8 {
+        (self.prev[row * self.words + col / 64] >> (col % 64) & 1) as u8
+    }
+
+    fn set_internal(&mut self, row: usize, col: usize, val: u8) {
+        let word = &mut self.prev[row * self.words + col / 64];
+        if val == 1 {
+            *word |= 1 << (col % 64);
+        } else {
+            *word &= !(1 << (col % 64));
+        }
+    }
+
+    fn fill_border(&mut self) {
+        let (width, height) = (self.width as i64, self.height as i64);
+
+        for r in 0..self.big_height {
+            // Only the first and last cell of the rows in between are border
+            let step = if r == 0 || r == self.height + 1 {
+                1
+            } else {
+                self.width + 1
+            };
+
+            for c in (0..self.width + 2).step_by(step) {
+                let val = match self
+                    .rule
+                    .topology
+                    .wrap(c as i64 - 1, r as i64 - 1, width, height)
+                {
+                    Some((x, y)) => self.get_internal(y as usize + 1, x as usize + 1),
+                    None => 0,
                 };
+                self.set_internal(r, c, val);
+            }
+        }
+    }
+
+    // Word k of a row, moved so that every bit holds the cell dx columns away
+    fn shifted(&self, row: usize, k: usize, dx: i32) -> u64 {
+        let words = &self.prev[row * self.words..(row + 1) * self.words];
+        match dx {
+            -1 => words[k] << 1 | if k > 0 { words[k - 1] >> 63 } else { 0 },
+            1 => words[k] >> 1 | words.get(k + 1).map_or(0, |w| w << 63),
+            _ => words[k],
+        }
+    }
+
+    fn step(&mut self) {
+        self.fill_border();
+
+        for r in 1..(self.height + 1) {
+            for k in 0..self.words {
+                // Neighbor count of all 64 cells, one bit of it per plane
+                let mut planes = [0u64; 4];
+                for &(dx, dy) in &self.neighbors {
+                    let mut carry = self.shifted((r as i32 + dy) as usize, k, dx);
+                    for plane in &mut planes {
+                        let next_carry = *plane & carry;
+                        *plane ^= carry;
+                        carry = next_carry;
+                    }
+                }
+
+                let mut born = 0;
+                let mut survives = 0;
+                for n in 0..=8 {
+                    if (self.birth | self.survive) >> n & 1 == 0 {
+                        continue;
+                    }
 
-                self.set_internal(r, c, val as u8);
+                    // Cells whose count is exactly n
+                    let count = planes.iter().enumerate().fold(!0, |acc, (p, &plane)| {
+                        acc & if n >> p & 1 == 1 { plane } else { !plane }
+                    });
+
+                    if self.birth >> n & 1 == 1 {
+                        born |= count;
+                    }
+                    if self.survive >> n & 1 == 1 {
+                        survives |= count;
+                    }
+                }
+
+                let alive = self.prev[r * self.words + k];
+                self.next[r * self.words + k] = (born & !alive | survives & alive) & self.inner[k];
             }
         }
 
-        self.swap_buffers();
+        std::mem::swap(&mut self.prev, &mut self.next);
+    }
+}
+
+impl Simulator for PackedGoL {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
     }
 
-    pub fn bench(&mut self, iterations: usize) {
-        self.fill_with_gliders();
+    fn set_rule(&mut self, rule: &Rule) -> Result<(), &'static str> {
+        let cells = self.read_state();
+        *self = PackedGoL::new(self.width, self.height, rule)?;
+        self.set_cells(Region::new(0, 0, self.width, self.height), &cells);
+        Ok(())
+    }
 
-        let start = Instant::now();
+    fn tick(&mut self, generations: u64) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
 
-        for _ in 0..iterations {
-            self.tick();
+    fn set_cells(&mut self, region: Region, data: &[u8]) {
+        for ((x, y), &cell) in region.cells().zip(data) {
+            self.set(y, x, cell);
         }
+    }
 
-        let stop = Instant::now();
-        let micro = stop.duration_since(start).as_micros() as f64;
-        let milli = micro / 1000.0;
+    fn read_state(&self) -> Vec<u8> {
+        self.iter().flatten().collect()
+    }
 
-        println!(
-            "Total: {:.6} ms ({:.6} ms / iter)",
-            milli,
-            milli / iterations as f64
-        );
+    fn population(&self) -> u64 {
+        // Leaving out the border
+        self.prev[self.words..(self.height + 1) * self.words]
+            .chunks(self.words)
+            .flat_map(|row| row.iter().zip(&self.inner))
+            .map(|(word, inner)| (word & inner).count_ones() as u64)
+            .sum()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::RangeInclusive;
 
-    pub fn print(&self, dead: &str, alive: &str) {
-        let mut output = String::with_capacity((dead.len() * self.width + 1) * self.height);
-        for row in self.iter() {
-            for cell in row {
-                if cell == 0 {
-                    output.push_str(dead);
-                } else {
-                    output.push_str(alive);
+    use super::*;
+    use crate::rule::Topology;
+
+    const TOPOLOGIES: [Topology; 4] = [
+        Topology::Torus,
+        Topology::Plane,
+        Topology::KleinBottle,
+        Topology::CrossSurface,
+    ];
+
+    fn rule(rule: &str, topology: Topology) -> Rule {
+        Rule {
+            topology,
+            ..rule.parse().unwrap()
+        }
+    }
+
+    #[test]
+    fn packed_matches_basic() {
+        // Rows of 1, 2 and 3 words with the border cells
+        for (width, height) in [(62, 9), (64, 17), (130, 37)] {
+            for name in ["B3/S23", "B36/S23", "B2/S", "B3678/S34678", "B1357/S1357"] {
+                for topology in TOPOLOGIES {
+                    let rule = rule(name, topology);
+                    let mut basic = BasicGoL::new(width, height, &rule);
+                    let mut packed = PackedGoL::new(width, height, &rule).unwrap();
+                    basic.randomize(0.4, 5);
+                    packed.randomize(0.4, 5);
+
+                    for generation in 1..=100 {
+                        basic.tick(1);
+                        packed.tick(1);
+                        assert!(
+                            packed.read_state() == basic.read_state(),
+                            "{} on a {:?} {}x{} at generation {}",
+                            name,
+                            topology,
+                            width,
+                            height,
+                            generation
+                        );
+                    }
+                    assert_eq!(packed.population(), basic.population());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn threads_match_one_thread() {
+        // Heights that don't split evenly into bands
+        for (width, height) in [(40, 37), (23, 11), (17, 5)] {
+            for name in ["B3/S23:P", "B36/S23:K", "R2,C3,M1,S4..8,B5..6,NM:K"] {
+                let rule: Rule = name.parse().unwrap();
+                let mut engines = [1, 2, 3, 7].map(|threads| {
+                    let mut basic = BasicGoL::new(width, height, &rule);
+                    basic.set_threads(threads);
+                    basic.randomize(0.4, 9);
+                    basic
+                });
+
+                for generation in 1..=60 {
+                    for basic in &mut engines {
+                        basic.tick(1);
+                    }
+
+                    let sequential = engines[0].read_state();
+                    for basic in &engines[1..] {
+                        assert!(
+                            basic.read_state() == sequential,
+                            "{} on {}x{} with {} threads at generation {}",
+                            name,
+                            width,
+                            height,
+                            basic.threads,
+                            generation
+                        );
+                    }
                 }
             }
-            output.push('\n');
         }
+    }
 
-        print!("{}", output);
+    // Column and row of every active tile
+    fn tiles(active: &[bool], columns: usize) -> Vec<(usize, usize)> {
+        (0..active.len())
+            .filter(|&i| active[i])
+            .map(|i| (i % columns, i / columns))
+            .collect()
+    }
+
+    // Tiles from column and row to column and row, rows first
+    fn square(columns: RangeInclusive<usize>, rows: RangeInclusive<usize>) -> Vec<(usize, usize)> {
+        rows.flat_map(|y| columns.clone().map(move |x| (x, y)))
+            .collect()
+    }
+
+    // Ticks both, the second one computing every tile, and checks they agree
+    fn tick_both(tracked: &mut BasicGoL, untracked: &mut BasicGoL, generation: u64) {
+        tracked.tick(1);
+        untracked.tiles.mark_all();
+        untracked.tick(1);
+        assert!(
+            tracked.read_state() == untracked.read_state(),
+            "generation {}",
+            generation
+        );
+    }
+
+    #[test]
+    fn still_lifes_go_idle() {
+        let rule = "B3/S23".parse().unwrap();
+        let mut basic = BasicGoL::new(192, 192, &rule);
+        // A block in tile (0, 0) and a blinker in tile (3, 3)
+        basic.set_cells(Region::new(10, 10, 2, 2), &[1; 4]);
+        basic.set_cells(Region::new(104, 110, 3, 1), &[1; 3]);
+
+        basic.tick(2);
+        assert_eq!(tiles(&basic.tiles.active(), 6), square(2..=4, 2..=4));
+        basic.tick(5);
+        assert_eq!(tiles(&basic.tiles.active(), 6), square(2..=4, 2..=4));
+        assert_eq!(basic.population(), 7);
+    }
+
+    #[test]
+    fn gliders_wake_the_tiles_ahead() {
+        let rule = "B3/S23:P".parse().unwrap();
+        let mut tracked = BasicGoL::new(128, 128, &rule);
+        let mut untracked = BasicGoL::new(128, 128, &rule);
+        // Heading down and right from tile (0, 0) into tile (1, 1)
+        let glider = [0, 1, 0, 0, 0, 1, 1, 1, 1];
+        tracked.set_cells(Region::new(24, 24, 3, 3), &glider);
+        untracked.set_cells(Region::new(24, 24, 3, 3), &glider);
+
+        for generation in 1..=2 {
+            tick_both(&mut tracked, &mut untracked, generation);
+        }
+        // Changes near the edges wake the whole edge, but not the middle
+        let active = tiles(&tracked.tiles.active(), 4);
+        assert!(active.contains(&(1, 1)));
+        assert!(!active.contains(&(2, 2)));
+
+        for generation in 3..=40 {
+            tick_both(&mut tracked, &mut untracked, generation);
+        }
+        // Moved 10 cells, across the tile edge
+        let state = tracked.read_state();
+        let moved = (34..37)
+            .flat_map(|y| &state[y * 128 + 34..y * 128 + 37])
+            .copied()
+            .collect::<Vec<u8>>();
+        assert_eq!(moved, glider);
+        assert!(tiles(&tracked.tiles.active(), 4).contains(&(2, 2)));
+        assert_eq!(tracked.population(), 5);
+    }
+
+    #[test]
+    fn pokes_wake_idle_tiles() {
+        let rule = "B3/S23".parse().unwrap();
+        let mut tracked = BasicGoL::new(192, 192, &rule);
+        let mut untracked = BasicGoL::new(192, 192, &rule);
+
+        for generation in 1..=2 {
+            tick_both(&mut tracked, &mut untracked, generation);
+        }
+        assert_eq!(tiles(&tracked.tiles.active(), 6), []);
+
+        // A blinker on the right edge of tile (2, 2), reaching into tile (3, 2)
+        for basic in [&mut tracked, &mut untracked] {
+            basic.set_cells(Region::new(95, 70, 1, 3), &[1; 3]);
+        }
+        assert_eq!(tiles(&tracked.tiles.active(), 6), square(1..=3, 1..=3));
+
+        for generation in 3..=10 {
+            tick_both(&mut tracked, &mut untracked, generation);
+        }
+        assert_eq!(tiles(&tracked.tiles.active(), 6), square(1..=4, 1..=3));
+    }
+
+    #[test]
+    fn packed_rejects_what_it_cant_run() {
+        for name in ["B2/S/C3", "B2n3/S23", "R2,C0,M0,S2..3,B3..3,NM"] {
+            assert!(
+                PackedGoL::new(8, 8, &name.parse().unwrap()).is_err(),
+                "{}",
+                name
+            );
+        }
     }
 }
@@ -0,0 +1,798 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+use crate::rule::{Rule, RuleKind, Topology, Transitions, NEIGHBORS};
+use crate::simulator::{Region, Simulator};
+
+// A square of 2^level by 2^level cells. Level 0 nodes are single cells, the
+// dead one has id 0 and the alive one id 1
+#[derive(Clone, Copy)]
+struct Node {
+    level: u8,
+    // Quadrants in the order nw, ne, sw, se
+    children: [u32; 4],
+    population: u64,
+}
+
+// Multiply and rotate hash for the small keys of node ids. Every jump looks
+// up every node again, which SipHash made most of the run time
+#[derive(Default)]
+struct NodeHasher(u64);
+
+impl NodeHasher {
+    fn add(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+impl Hasher for NodeHasher {
+    // The multiply leaves the low bits, which pick the bucket, the worst mixed
+    fn finish(&self) -> u64 {
+        self.0.rotate_left(26)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut n = [0; 8];
+            n[..chunk.len()].copy_from_slice(chunk);
+            self.add(u64::from_le_bytes(n));
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.add(n as u64);
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.add(n as u64);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.add(n as u64);
+    }
+}
+
+// Memoized quadtree engine, every distinct node is stored once and what it
+// turns into is only ever computed once. That lets it jump 2^k generations
+// at a time over patterns that repeat in space or time. The universe is an
+// unbounded plane, with rows going down like in BasicGoL
+pub struct HashLife {
+    birth: Transitions,
+    survive: Transitions,
+
+    nodes: Vec<Node>,
+    ids: HashMap<[u32; 4], u32, BuildHasherDefault<NodeHasher>>,
+    // The centre half of a node after 2^j generations, by node and j
+    results: HashMap<(u32, u8), u32, BuildHasherDefault<NodeHasher>>,
+    // The empty node of each level
+    empty: Vec<u32>,
+
+    root: u32,
+    // Cell at the top left corner of the root
+    origin: (i64, i64),
+    generation: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct Snapshot {
+    root: u32,
+    origin: (i64, i64),
+    generation: u64,
+}
+
+impl HashLife {
+    pub fn new(rule: &Rule) -> Result<Self, &'static str> {
+        let (birth, survive) = match rule.kind {
+            RuleKind::Life { birth, survive, .. } if rule.states == 2 => (birth, survive),
+            RuleKind::Life { .. } => return Err("HashLife can't run Generations rules"),
+            RuleKind::LargerThanLife(_) => return Err("HashLife can't run Larger than Life rules"),
+        };
+
+        if birth.contains(0) {
+            return Err("HashLife can't run rules with B0, empty space has to stay empty");
+        }
+
+        let leaf = |population| Node {
+            level: 0,
+            children: [0; 4],
+            population,
+        };
+
+        let mut life = HashLife {
+            birth,
+            survive,
+
+            nodes: vec![leaf(0), leaf(1)],
+            ids: HashMap::default(),
+            results: HashMap::default(),
+            empty: vec![0],
+
+            root: 0,
+            origin: (0, 0),
+            generation: 0,
+        };
+
+        life.root = life.empty(3);
+        life.origin = (-4, -4);
+        Ok(life)
+    }
+
+    // Makes a universe out of a flat grid of cells, rows first, with its top
+    // left corner at (0, 0)
+    pub fn from_cells(
+        rule: &Rule,
+        width: usize,
+        height: usize,
+        cells: &[u8],
+    ) -> Result<Self, &'static str> {
+        let mut life = HashLife::new(rule)?;
+        life.load(width, height, cells);
+        Ok(life)
+    }
+
+    // Replaces the universe with a flat grid of cells, see from_cells
+    pub fn load(&mut self, width: usize, height: usize, cells: &[u8]) {
+        let size = width.max(height).max(8).next_power_of_two();
+        let level = size.trailing_zeros() as u8;
+
+        self.root = self.build(level, 0, 0, width, height, cells);
+        self.origin = (0, 0);
+        self.generation = 0;
+    }
+
+    fn build(
+        &mut self,
+        level: u8,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        cells: &[u8],
+    ) -> u32 {
+        if x >= width || y >= height {
+            return self.empty(level);
+        }
+        if level == 0 {
+            return (cells[y * width + x] == 1) as u32;
+        }
+
+        let half = 1 << (level - 1);
+        let mut children = [0; 4];
+        for (i, child) in children.iter_mut().enumerate() {
+            let (dx, dy) = (i % 2 * half, i / 2 * half);
+            *child = self.build(level - 1, x + dx, y + dy, width, height, cells);
+        }
+
+        self.join(children)
+    }
+
+    // The cells in a window of the universe as a flat grid, rows first
+    pub fn to_cells(&self, x: i64, y: i64, width: usize, height: usize) -> Vec<u8> {
+        let mut cells = vec![0; width * height];
+        let window = (x, y, width as i64, height as i64);
+        self.fill(self.root, self.origin, window, &mut cells);
+        cells
+    }
+
+    fn fill(&self, id: u32, (x, y): (i64, i64), window: (i64, i64, i64, i64), cells: &mut [u8]) {
+        let node = self.nodes[id as usize];
+        let size = 1i64 << node.level;
+        let (wx, wy, width, height) = window;
+
+        if node.population == 0
+            || x + size <= wx
+            || y + size <= wy
+            || x >= wx + width
+            || y >= wy + height
+        {
+            return;
+        }
+
+        if node.level == 0 {
+            cells[((y - wy) * width + x - wx) as usize] = 1;
+            return;
+        }
+
+        let half = size / 2;
+        for (i, &child) in node.children.iter().enumerate() {
+            let corner = (x + i as i64 % 2 * half, y + i as i64 / 2 * half);
+            self.fill(child, corner, window, cells);
+        }
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> u8 {
+        let (mut x, mut y) = (x - self.origin.0, y - self.origin.1);
+        let mut node = self.nodes[self.root as usize];
+        let size = 1i64 << node.level;
+
+        if x < 0 || y < 0 || x >= size || y >= size {
+            return 0;
+        }
+
+        while node.level > 0 {
+            let half = 1i64 << (node.level - 1);
+            let i = (y >= half) as usize * 2 + (x >= half) as usize;
+            node = self.nodes[node.children[i] as usize];
+            (x, y) = (x % half, y % half);
+        }
+
+        node.population as u8
+    }
+
+    // Writes a flat grid of cells, rows first, with its top left corner at
+    // (x, y). The cells around it stay as they are
+    pub fn paste(&mut self, x: i64, y: i64, width: usize, height: usize, cells: &[u8]) {
+        let (right, bottom) = (x + width as i64, y + height as i64);
+        loop {
+            let size = 1i64 << self.nodes[self.root as usize].level;
+            let (left, top) = self.origin;
+            if x >= left && y >= top && right <= left + size && bottom <= top + size {
+                break;
+            }
+            self.expand();
+        }
+
+        let window = (x, y, width as i64, height as i64);
+        self.root = self.paste_in(self.root, self.origin, window, cells);
+    }
+
+    fn paste_in(
+        &mut self,
+        id: u32,
+        (x, y): (i64, i64),
+        window: (i64, i64, i64, i64),
+        cells: &[u8],
+    ) -> u32 {
+        let node = self.nodes[id as usize];
+        let size = 1i64 << node.level;
+        let (wx, wy, width, height) = window;
+
+        if x + size <= wx || y + size <= wy || x >= wx + width || y >= wy + height {
+            return id;
+        }
+        if node.level == 0 {
+            return (cells[((y - wy) * width + x - wx) as usize] == 1) as u32;
+        }
+
+        let half = size / 2;
+        let mut children = node.children;
+        for (i, child) in children.iter_mut().enumerate() {
+            let corner = (x + i as i64 % 2 * half, y + i as i64 / 2 * half);
+            *child = self.paste_in(*child, corner, window, cells);
+        }
+        self.join(children)
+    }
+
+    // Kills every cell outside a window of the universe
+    pub fn crop(&mut self, x: i64, y: i64, width: usize, height: usize) {
+        let window = (x, y, width as i64, height as i64);
+        self.root = self.crop_in(self.root, self.origin, window);
+    }
+
+    fn crop_in(&mut self, id: u32, (x, y): (i64, i64), window: (i64, i64, i64, i64)) -> u32 {
+        let node = self.nodes[id as usize];
+        let size = 1i64 << node.level;
+        let (wx, wy, width, height) = window;
+
+        if node.population == 0
+            || x >= wx && y >= wy && x + size <= wx + width && y + size <= wy + height
+        {
+            return id;
+        }
+        if x + size <= wx || y + size <= wy || x >= wx + width || y >= wy + height {
+            return self.empty(node.level);
+        }
+
+        let half = size / 2;
+        let mut children = node.children;
+        for (i, child) in children.iter_mut().enumerate() {
+            let corner = (x + i as i64 % 2 * half, y + i as i64 / 2 * half);
+            *child = self.crop_in(*child, corner, window);
+        }
+        self.join(children)
+    }
+
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root as usize].population
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // Where the universe is at now, to go back to later. Nodes never change,
+    // so this stays good until the universe forgets them
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            root: self.root,
+            origin: self.origin,
+            generation: self.generation,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.root = snapshot.root;
+        self.origin = snapshot.origin;
+        self.generation = snapshot.generation;
+    }
+
+    // Advances any number of generations, as jumps of powers of two
+    pub fn step(&mut self, generations: u64) {
+        for j in 0..64 {
+            if generations >> j & 1 == 1 {
+                self.jump(j);
+            }
+        }
+    }
+
+    fn jump(&mut self, j: u8) {
+        // The pattern has to sit in the middle quarter of the root, so that
+        // it can't grow out of the centre half that is left after the jump
+        while self.nodes[self.root as usize].level < j + 3 || !self.centred() {
+            self.expand();
+        }
+        self.expand();
+
+        let size = 1i64 << self.nodes[self.root as usize].level;
+        self.root = self.result(self.root, j);
+        self.origin = (self.origin.0 + size / 4, self.origin.1 + size / 4);
+        self.generation += 1 << j;
+    }
+
+    fn forget(&mut self) {
+        self.nodes.truncate(2);
+        self.ids.clear();
+        self.results.clear();
+        self.empty.truncate(1);
+        self.root = self.empty(3);
+        self.origin = (-4, -4);
+    }
+
+    fn join(&mut self, children: [u32; 4]) -> u32 {
+        if let Some(&id) = self.ids.get(&children) {
+            return id;
+        }
+
+        let level = self.nodes[children[0] as usize].level + 1;
+        let population = children
+            .iter()
+            .map(|&child| self.nodes[child as usize].population)
+            .sum();
+
+        let id = self.nodes.len() as u32;
+        self.nodes.push(Node {
+            level,
+            children,
+            population,
+        });
+        self.ids.insert(children, id);
+        id
+    }
+
+    fn empty(&mut self, level: u8) -> u32 {
+        while self.empty.len() <= level as usize {
+            let empty = *self.empty.last().unwrap();
+            let bigger = self.join([empty; 4]);
+            self.empty.push(bigger);
+        }
+
+        self.empty[level as usize]
+    }
+
+    fn child(&self, id: u32, quadrant: usize) -> u32 {
+        self.nodes[id as usize].children[quadrant]
+    }
+
+    fn centre(&mut self, id: u32) -> u32 {
+        let [nw, ne, sw, se] = self.nodes[id as usize].children;
+        let children = [
+            self.child(nw, 3),
+            self.child(ne, 2),
+            self.child(sw, 1),
+            self.child(se, 0),
+        ];
+        self.join(children)
+    }
+
+    // Whether all live cells of the root are in its centre half
+    fn centred(&self) -> bool {
+        let population = |id: u32| self.nodes[id as usize].population;
+        let children = self.nodes[self.root as usize].children;
+
+        children
+            .iter()
+            .enumerate()
+            .all(|(i, &child)| population(child) == population(self.child(child, 3 - i)))
+    }
+
+    // Doubles the size of the root, keeping it centred on the same cells
+    fn expand(&mut self) {
+        let node = self.nodes[self.root as usize];
+        let empty = self.empty(node.level - 1);
+        let [nw, ne, sw, se] = node.children;
+
+        let children = [
+            self.join([empty, empty, empty, nw]),
+            self.join([empty, empty, ne, empty]),
+            self.join([empty, sw, empty, empty]),
+            self.join([se, empty, empty, empty]),
+        ];
+        self.root = self.join(children);
+
+        let half = 1i64 << (node.level - 1);
+        self.origin = (self.origin.0 - half, self.origin.1 - half);
+    }
+
+    // The centre half of a node 2^j generations later, j being at most the
+    // node's level minus two
+    fn result(&mut self, id: u32, j: u8) -> u32 {
+        if let Some(&result) = self.results.get(&(id, j)) {
+            return result;
+        }
+
+        let node = self.nodes[id as usize];
+        let result = if node.population == 0 {
+            self.empty(node.level - 1)
+        } else if node.level == 2 {
+            self.base(id)
+        } else {
+            let [nw, ne, sw, se] = node.children;
+            // Nine overlapping nodes of half the size, then their centres
+            // either moved forward by half the jump or not at all
+            let mut nine = [
+                nw,
+                self.join([
+                    self.child(nw, 1),
+                    self.child(ne, 0),
+                    self.child(nw, 3),
+                    self.child(ne, 2),
+                ]),
+                ne,
+                self.join([
+                    self.child(nw, 2),
+                    self.child(nw, 3),
+                    self.child(sw, 0),
+                    self.child(sw, 1),
+                ]),
+                self.join([
+                    self.child(nw, 3),
+                    self.child(ne, 2),
+                    self.child(sw, 1),
+                    self.child(se, 0),
+                ]),
+                self.join([
+                    self.child(ne, 2),
+                    self.child(ne, 3),
+                    self.child(se, 0),
+                    self.child(se, 1),
+                ]),
+                sw,
+                self.join([
+                    self.child(sw, 1),
+                    self.child(se, 0),
+                    self.child(sw, 3),
+                    self.child(se, 2),
+                ]),
+                se,
+            ];
+
+            let full = j + 2 == node.level;
+            for part in &mut nine {
+                *part = if full {
+                    self.result(*part, j - 1)
+                } else {
+                    self.centre(*part)
+                };
+            }
+
+            // And the four quadrants of the result from the rest of the jump
+            let mut quadrants = [[0, 1, 3, 4], [1, 2, 4, 5], [3, 4, 6, 7], [4, 5, 7, 8]]
+                .map(|parts| parts.map(|i| nine[i]))
+                .map(|children| self.join(children));
+            for quadrant in &mut quadrants {
+                *quadrant = self.result(*quadrant, if full { j - 1 } else { j });
+            }
+
+            self.join(quadrants)
+        };
+
+        self.results.insert((id, j), result);
+        result
+    }
+
+    // The middle 2 by 2 cells of a 4 by 4 node one generation later
+    fn base(&mut self, id: u32) -> u32 {
+        let mut cells = [[0u8; 4]; 4];
+        for (i, &quadrant) in self.nodes[id as usize].children.iter().enumerate() {
+            for (j, &leaf) in self.nodes[quadrant as usize].children.iter().enumerate() {
+                cells[i / 2 * 2 + j / 2][i % 2 * 2 + j % 2] = leaf as u8;
+            }
+        }
+
+        let next = [(1, 1), (1, 2), (2, 1), (2, 2)].map(|(r, c): (i32, i32)| {
+            let config = NEIGHBORS.iter().fold(0, |config, &(x, y)| {
+                config << 1 | cells[(r + y) as usize][(c + x) as usize]
+            });
+
+            let alive = if cells[r as usize][c as usize] == 1 {
+                self.survive.contains(config)
+            } else {
+                self.birth.contains(config)
+            };
+            alive as u32
+        });
+
+        self.join(next)
+    }
+}
+
+// HashLife running a board like the other engines do, with the edges joined
+// by the rule's topology. Before a jump the board gets a margin of the cells
+// that the topology glues to its edges, which keeps the board right for as
+// many generations as the margin is wide. Cells are only ever on the board
+// between jumps
+pub struct HashLifeBoard {
+    life: HashLife,
+    width: usize,
+    height: usize,
+    topology: Topology,
+}
+
+impl HashLifeBoard {
+    pub fn new(width: usize, height: usize, rule: &Rule) -> Result<Self, &'static str> {
+        match rule.topology {
+            Topology::Plane => return Err("HashLife can't run a bounded plane"),
+            // Cells in mirrored copies see their neighborhood mirrored
+            Topology::KleinBottle | Topology::CrossSurface if rule.is_hexagonal() => {
+                return Err("HashLife can't mirror hexagonal rules across twisted edges")
+            }
+            _ => {}
+        }
+
+        let mut board = HashLifeBoard {
+            life: HashLife::new(rule)?,
+            width,
+            height,
+            topology: rule.topology,
+        };
+        board.life.load(width, height, &vec![0; width * height]);
+        Ok(board)
+    }
+}
+
+impl Simulator for HashLifeBoard {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn set_rule(&mut self, rule: &Rule) -> Result<(), &'static str> {
+        let cells = self.read_state();
+        *self = HashLifeBoard::new(self.width, self.height, rule)?;
+        self.life.load(self.width, self.height, &cells);
+        Ok(())
+    }
+
+    fn tick(&mut self, generations: u64) {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let mut left = generations;
+
+        while left > 0 {
+            if self.life.nodes.len() > 1 << 24 {
+                let cells = self.read_state();
+                self.life.forget();
+                self.life.load(self.width, self.height, &cells);
+            }
+
+            // Light can't cross more than the margin in the meantime
+            let margin = left.min(width.min(height) as u64) as i64;
+            let strips = [
+                (-margin, -margin, width + 2 * margin, margin),
+                (-margin, height, width + 2 * margin, margin),
+                (-margin, 0, margin, height),
+                (width, 0, margin, height),
+            ];
+            for (x, y, strip_width, strip_height) in strips {
+                let cells = (0..strip_width * strip_height)
+                    .map(|i| {
+                        let (x, y) = (x + i % strip_width, y + i / strip_width);
+                        let (x, y) = self.topology.wrap(x, y, width, height).unwrap();
+                        self.life.get(x, y)
+                    })
+                    .collect::<Vec<u8>>();
+                self.life
+                    .paste(x, y, strip_width as usize, strip_height as usize, &cells);
+            }
+
+            self.life.step(margin as u64);
+            self.life.crop(0, 0, self.width, self.height);
+            left -= margin as u64;
+        }
+    }
+
+    fn set_cells(&mut self, region: Region, data: &[u8]) {
+        if (region.width, region.height) == (self.width, self.height) {
+            self.life.load(self.width, self.height, data);
+            return;
+        }
+
+        self.life.paste(
+            region.x as i64,
+            region.y as i64,
+            region.width,
+            region.height,
+            data,
+        );
+    }
+
+    fn read_state(&self) -> Vec<u8> {
+        self.life.to_cells(0, 0, self.width, self.height)
+    }
+
+    fn population(&self) -> u64 {
+        self.life.population()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_of_life::BasicGoL;
+    use crate::library;
+
+    fn rule(rule: &str) -> Rule {
+        rule.parse().unwrap()
+    }
+
+    fn library(name: &str) -> HashLife {
+        let pattern = library::find(name).unwrap().pattern();
+        HashLife::from_cells(
+            &rule("B3/S23"),
+            pattern.width,
+            pattern.height,
+            &pattern.cells,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn board_matches_basic() {
+        for name in ["B3/S23:T", "B36/S23:T", "B3/S23:K", "B3678/S34678:C"] {
+            let rule = rule(name);
+            let mut basic = BasicGoL::new(37, 23, &rule);
+            let mut hashlife = HashLifeBoard::new(37, 23, &rule).unwrap();
+            basic.randomize(0.4, 3);
+            hashlife.set_cells(Region::new(0, 0, 37, 23), &basic.read_state());
+
+            // Jumps shorter and longer than the board, as wide margins
+            for jump in [1, 2, 7, 16, 30, 64] {
+                basic.tick(jump);
+                hashlife.tick(jump);
+                assert!(
+                    hashlife.read_state() == basic.read_state(),
+                    "{} after a jump of {}",
+                    name,
+                    jump
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn plane_matches_basic() {
+        // A patch in the middle of a bounded plane, which doesn't get near its
+        // edges within the generations checked
+        let (size, patch, generations) = (120, 20, 40);
+        let rule = rule("B3/S23:P");
+        let mut cells = BasicGoL::new(patch, patch, &rule);
+        cells.randomize(0.5, 11);
+        let mut basic = BasicGoL::new(size, size, &rule);
+        let corner = (size - patch) / 2;
+        basic.set_cells(
+            Region::new(corner, corner, patch, patch),
+            &cells.read_state(),
+        );
+        let start = basic.read_state();
+
+        let mut one_at_a_time = HashLife::from_cells(&rule, size, size, &start).unwrap();
+        for generation in 1..=generations {
+            basic.tick(1);
+            one_at_a_time.step(1);
+            assert!(
+                one_at_a_time.to_cells(0, 0, size, size) == basic.read_state(),
+                "generation {}",
+                generation
+            );
+        }
+        assert_eq!(one_at_a_time.generation(), generations);
+
+        let mut at_once = HashLife::from_cells(&rule, size, size, &start).unwrap();
+        at_once.step(generations);
+        assert!(at_once.to_cells(0, 0, size, size) == basic.read_state());
+        assert_eq!(at_once.population(), basic.population());
+    }
+
+    #[test]
+    fn methuselahs_settle() {
+        // Generation the population stops changing at, and what it is then
+        for (name, settled, population) in [
+            ("acorn", 5206, 633),
+            ("r-pentomino", 1103, 116),
+            ("diehard", 130, 0),
+        ] {
+            let mut life = library(name);
+            life.step(settled - 1);
+            assert_ne!(life.population(), population, "{}", name);
+            life.step(1);
+            assert_eq!(life.population(), population, "{}", name);
+
+            // Still lifes and gliders from then on
+            for _ in 0..10 {
+                life.step(1);
+                assert_eq!(life.population(), population, "{}", name);
+            }
+            life.step(1000);
+            assert_eq!(life.population(), population, "{}", name);
+            assert_eq!(life.generation(), settled + 1010);
+        }
+    }
+
+    #[test]
+    fn rejects_what_it_cant_run() {
+        for (rule, error) in [
+            ("B2/S/C3", "HashLife can't run Generations rules"),
+            (
+                "R5,C0,M1,S34..58,B34..45,NM",
+                "HashLife can't run Larger than Life rules",
+            ),
+            (
+                "B03/S23",
+                "HashLife can't run rules with B0, empty space has to stay empty",
+            ),
+        ] {
+            assert_eq!(HashLife::new(&self::rule(rule)).err(), Some(error));
+            assert_eq!(
+                HashLifeBoard::new(16, 16, &self::rule(rule)).err(),
+                Some(error)
+            );
+        }
+
+        assert_eq!(
+            HashLifeBoard::new(16, 16, &rule("B3/S23:P")).err(),
+            Some("HashLife can't run a bounded plane")
+        );
+    }
+
+    #[test]
+    fn board_crops_after_a_jump() {
+        // Gliders heading off every edge, which the margins copy outside of it
+        let (width, height) = (24, 20);
+        let glider = library::find("glider").unwrap().pattern();
+        let mut board = HashLifeBoard::new(width, height, &rule("B3/S23:T")).unwrap();
+        for (x, y) in [(0, 0), (12, 4), (4, 14), (18, 15)] {
+            board.set_cells(
+                Region::new(x, y, glider.width, glider.height),
+                &glider.cells,
+            );
+        }
+
+        for jump in [5, 20, 64] {
+            board.tick(jump);
+            let cells = board.read_state();
+            let alive = cells.iter().filter(|&&cell| cell == 1).count() as u64;
+            assert_eq!(board.population(), alive);
+
+            // Nothing is left in the margin around the board
+            let margin = 64;
+            let around = board.life.to_cells(
+                -margin,
+                -margin,
+                width + 2 * margin as usize,
+                height + 2 * margin as usize,
+            );
+            assert_eq!(
+                around.iter().filter(|&&cell| cell == 1).count() as u64,
+                alive
+            );
+        }
+    }
+}
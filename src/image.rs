@@ -0,0 +1,329 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::Color;
+
+// Writes pixels given in rows going down as a binary PPM, which has no alpha
+pub fn write_ppm(path: &Path, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "P6\n{} {}\n255\n", width, height)?;
+
+    let bytes = pixels
+        .iter()
+        .flat_map(|c| [c.0, c.1, c.2])
+        .collect::<Vec<u8>>();
+    file.write_all(&bytes)?;
+    file.flush()
+}
+
+// Writes pixels given in rows going down as an 8 bit RGBA PNG
+pub fn write_png(path: &Path, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, adaptive filtering, no interlacing
+    header.extend([8, 6, 0, 0, 0]);
+    write_chunk(&mut file, b"IHDR", &header)?;
+
+    // Every row starts with its filter type, 0 for none
+    let mut raw = Vec::with_capacity((width * 4 + 1) * height);
+    for row in pixels.chunks(width) {
+        raw.push(0);
+        raw.extend(row.iter().flat_map(|c| [c.0, c.1, c.2, c.3]));
+    }
+    write_chunk(&mut file, b"IDAT", &zlib(&raw))?;
+
+    write_chunk(&mut file, b"IEND", &[])?;
+    file.flush()
+}
+
+fn write_chunk(file: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    file.write_all(&(data.len() as u32).to_be_bytes())?;
+    file.write_all(kind)?;
+    file.write_all(data)?;
+
+    let crc = !crc32(crc32(!0, kind), data);
+    file.write_all(&crc.to_be_bytes())
+}
+
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // Sums can't overflow for this many bytes before taking the modulo
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+// Boards are drawn with big runs of the same few colors, so plain LZ77 with
+// the fixed Huffman codes of deflate already shrinks them a lot
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    // Single final block with fixed codes
+    bits.write(0b1, 1);
+    bits.write(0b01, 2);
+
+    const WINDOW: usize = 32768;
+    const MAX_LENGTH: usize = 258;
+    // Last position that every hash of 3 bytes was seen at
+    let mut last = vec![usize::MAX; 1 << 15];
+    let hash = |i: usize| {
+        ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & 0x7FFF
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut length = 0;
+        let mut distance = 0;
+
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            let candidate = last[h];
+            last[h] = i;
+
+            if candidate != usize::MAX && i - candidate <= WINDOW {
+                let max = MAX_LENGTH.min(data.len() - i);
+                length = (0..max)
+                    .take_while(|&k| data[candidate + k] == data[i + k])
+                    .count();
+                distance = i - candidate;
+            }
+        }
+
+        if length >= 3 {
+            write_length(&mut bits, length);
+            write_distance(&mut bits, distance);
+            for j in i + 1..(i + length).min(data.len().saturating_sub(2)) {
+                last[hash(j)] = j;
+            }
+            i += length;
+        } else {
+            write_literal(&mut bits, data[i] as u32);
+            i += 1;
+        }
+    }
+    write_literal(&mut bits, 256);
+
+    let mut out = vec![0x78, 0x01];
+    out.extend(bits.finish());
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+// Fixed Huffman code of a literal, the end of block or a length symbol
+fn write_literal(bits: &mut BitWriter, symbol: u32) {
+    let (code, len) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xC0 + symbol - 280, 8),
+    };
+    bits.write_code(code, len);
+}
+
+const LENGTH_BASES: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+fn write_length(bits: &mut BitWriter, length: usize) {
+    let i = LENGTH_BASES
+        .iter()
+        .rposition(|&base| base <= length)
+        .unwrap();
+    write_literal(bits, 257 + i as u32);
+    bits.write((length - LENGTH_BASES[i]) as u32, LENGTH_EXTRA[i]);
+}
+
+fn write_distance(bits: &mut BitWriter, distance: usize) {
+    let i = DISTANCE_BASES
+        .iter()
+        .rposition(|&base| base <= distance)
+        .unwrap();
+    bits.write_code(i as u32, 5);
+    bits.write((distance - DISTANCE_BASES[i]) as u32, DISTANCE_EXTRA[i]);
+}
+
+// Deflate packs bits starting from the least significant bit of every byte
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    // Values go in least significant bit first
+    fn write(&mut self, value: u32, len: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += len;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes go in most significant bit first
+    fn write_code(&mut self, code: u32, len: u32) {
+        self.write(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    // Decodes the fixed Huffman blocks zlib writes
+    fn inflate(data: &[u8]) -> Vec<u8> {
+        assert_eq!(data[..2], [0x78, 0x01]);
+        let mut bits = data[2..]
+            .iter()
+            .flat_map(|&byte| (0..8).map(move |i| (byte >> i & 1) as usize));
+        // Values come least significant bit first, Huffman codes most
+        let mut read = |len: usize, code: bool| {
+            (0..len).fold(0, |value, i| {
+                let bit = bits.next().expect("Ran out of bits");
+                if code {
+                    value << 1 | bit
+                } else {
+                    value | bit << i
+                }
+            })
+        };
+
+        let mut out = Vec::new();
+        loop {
+            let last = read(1, false) == 1;
+            assert_eq!(read(2, false), 0b01, "Not a fixed Huffman block");
+
+            loop {
+                let mut code = read(7, true);
+                let symbol = if code <= 0b0010111 {
+                    256 + code
+                } else {
+                    code = code << 1 | read(1, true);
+                    match code {
+                        0x30..=0xBF => code - 0x30,
+                        0xC0..=0xC7 => 280 + code - 0xC0,
+                        _ => 144 + (code << 1 | read(1, true)) - 0x190,
+                    }
+                };
+
+                match symbol {
+                    0..=255 => out.push(symbol as u8),
+                    256 => break,
+                    _ => {
+                        let i = symbol - 257;
+                        let length = LENGTH_BASES[i] + read(LENGTH_EXTRA[i] as usize, false);
+                        let i = read(5, true);
+                        let distance = DISTANCE_BASES[i] + read(DISTANCE_EXTRA[i] as usize, false);
+                        for _ in 0..length {
+                            out.push(out[out.len() - distance]);
+                        }
+                    }
+                }
+            }
+
+            if last {
+                break;
+            }
+        }
+
+        assert_eq!(data[data.len() - 4..], adler32(&out).to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(!crc32(!0, b"123456789"), 0xCBF43926);
+        // In pieces like a chunk's type and data
+        assert_eq!(!crc32(crc32(!0, b"1234"), b"56789"), 0xCBF43926);
+        assert_eq!(adler32(b"123456789"), 0x091E01DE);
+        // Past where the sums have to be taken modulo
+        assert_eq!(adler32(&[0xFF; 100_000]), 0x149A302C);
+    }
+
+    #[test]
+    fn zlib_known_bytes() {
+        // Checked with Python's zlib.decompress
+        assert_eq!(zlib(b""), [0x78, 0x01, 3, 0, 0, 0, 0, 1]);
+        assert_eq!(
+            zlib(b"Hello, hello, hello!"),
+            [120, 1, 243, 72, 205, 201, 201, 215, 81, 200, 64, 162, 20, 1, 72, 158, 6, 214]
+        );
+    }
+
+    #[test]
+    fn zlib_round_trips() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let noise = (0..40_000).map(|_| rng.gen()).collect::<Vec<u8>>();
+        // Few colors in long runs, like a board
+        let runs = (0..60_000)
+            .map(|i| [0, 255, 7][i / 1000 % 3] ^ (i % 4 == 0) as u8)
+            .collect::<Vec<u8>>();
+        // Repeats as far back as the window goes, and just past it
+        let far = [&noise[..32_768], &noise[..32_768], &noise[..1000]].concat();
+        let further = [&noise[..32_769], &noise[..32_769]].concat();
+
+        for data in [
+            &b"a"[..],
+            b"ab",
+            b"abcabcabcabcabcabc",
+            &[0; 1000],
+            &noise,
+            &runs,
+            &far,
+            &further,
+        ] {
+            assert!(inflate(&zlib(data)) == data, "{} bytes", data.len());
+        }
+
+        // And it does shrink runs
+        assert!(zlib(&runs).len() < runs.len() / 20);
+    }
+}
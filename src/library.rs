@@ -0,0 +1,384 @@
+use std::fmt;
+
+use gumdrop::Options;
+
+use crate::pattern::Pattern;
+
+#[derive(Debug, Options)]
+pub struct PatternsOptions {
+    #[options(help = "print help message")]
+    help: bool,
+}
+
+// A pattern that comes with the wallpaper, addressable by name
+pub struct Entry {
+    pub name: &'static str,
+    // Still life, oscillator, spaceship, gun...
+    pub kind: &'static str,
+    // Rule the pattern is meant for
+    pub rule: &'static str,
+    // Generations until it repeats, moved for spaceships and puffers
+    pub period: Option<u32>,
+    pub speed: Option<&'static str>,
+    rle: &'static str,
+}
+
+pub const LIBRARY: &[Entry] = &[
+    Entry {
+        name: "block",
+        kind: "still life",
+        rule: "B3/S23",
+        period: None,
+        speed: None,
+        rle: "2o$2o!",
+    },
+    Entry {
+        name: "beehive",
+        kind: "still life",
+        rule: "B3/S23",
+        period: None,
+        speed: None,
+        rle: "b2o$o2bo$b2o!",
+    },
+    Entry {
+        name: "blinker",
+        kind: "oscillator",
+        rule: "B3/S23",
+        period: Some(2),
+        speed: None,
+        rle: "3o!",
+    },
+    Entry {
+        name: "toad",
+        kind: "oscillator",
+        rule: "B3/S23",
+        period: Some(2),
+        speed: None,
+        rle: "b3o$3o!",
+    },
+    Entry {
+        name: "beacon",
+        kind: "oscillator",
+        rule: "B3/S23",
+        period: Some(2),
+        speed: None,
+        rle: "2o$o$3bo$2b2o!",
+    },
+    Entry {
+        name: "pulsar",
+        kind: "oscillator",
+        rule: "B3/S23",
+        period: Some(3),
+        speed: None,
+        rle: "2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$o4bobo4bo$\
+              o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+    },
+    Entry {
+        name: "pentadecathlon",
+        kind: "oscillator",
+        rule: "B3/S23",
+        period: Some(15),
+        speed: None,
+        rle: "2bo4bo$2ob4ob2o$2bo4bo!",
+    },
+    Entry {
+        name: "glider",
+        kind: "spaceship",
+        rule: "B3/S23",
+        period: Some(4),
+        speed: Some("c/4"),
+        rle: "bo$2bo$3o!",
+    },
+    Entry {
+        name: "lwss",
+        kind: "spaceship",
+        rule: "B3/S23",
+        period: Some(4),
+        speed: Some("c/2"),
+        rle: "bo2bo$o$o3bo$4o!",
+    },
+    Entry {
+        name: "mwss",
+        kind: "spaceship",
+        rule: "B3/S23",
+        period: Some(4),
+        speed: Some("c/2"),
+        rle: "3bo$bo3bo$o$o4bo$5o!",
+    },
+    Entry {
+        name: "hwss",
+        kind: "spaceship",
+        rule: "B3/S23",
+        period: Some(4),
+        speed: Some("c/2"),
+        rle: "3b2o$bo4bo$o$o5bo$6o!",
+    },
+    Entry {
+        name: "copperhead",
+        kind: "spaceship",
+        rule: "B3/S23",
+        period: Some(10),
+        speed: Some("c/10"),
+        rle: "b2o2b2o$3b2o$3b2o$obo2bobo$o6bo2$o6bo$b2o2b2o$2b4o2$3b2o$3b2o!",
+    },
+    Entry {
+        name: "gosper-gun",
+        kind: "gun",
+        rule: "B3/S23",
+        period: Some(30),
+        speed: None,
+        rle: "24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$2o8bo3bob2o4bobo$\
+              10bo5bo7bo$11bo3bo$12b2o!",
+    },
+    Entry {
+        name: "simkin-gun",
+        kind: "gun",
+        rule: "B3/S23",
+        period: Some(120),
+        speed: None,
+        rle: "2o5b2o$2o5b2o2$4b2o$4b2o5$22b2ob2o$21bo5bo$21bo6bo2b2o$21b3o3bo3b2o$\
+              26bo4$20b2o$20bo$21b3o$23bo!",
+    },
+    Entry {
+        name: "puffer-train",
+        kind: "puffer",
+        rule: "B3/S23",
+        period: Some(140),
+        speed: Some("c/2"),
+        rle: "3bo$4bo$o3bo$b4o4$o$b2o$2bo$2bo$bo3$3bo$4bo$o3bo$b4o!",
+    },
+    Entry {
+        name: "r-pentomino",
+        kind: "methuselah",
+        rule: "B3/S23",
+        period: None,
+        speed: None,
+        rle: "b2o$2o$bo!",
+    },
+    Entry {
+        name: "acorn",
+        kind: "methuselah",
+        rule: "B3/S23",
+        period: None,
+        speed: None,
+        rle: "bo$3bo$2o2b3o!",
+    },
+    Entry {
+        name: "diehard",
+        kind: "methuselah",
+        rule: "B3/S23",
+        period: None,
+        speed: None,
+        rle: "6bo$2o$bo3b3o!",
+    },
+    Entry {
+        name: "infinite-growth",
+        kind: "growth",
+        rule: "B3/S23",
+        period: None,
+        speed: None,
+        rle: "8ob5o3b3o6b7ob5o!",
+    },
+    Entry {
+        name: "replicator",
+        kind: "replicator",
+        rule: "B36/S23",
+        period: Some(12),
+        speed: None,
+        rle: "2b3o$bo2bo$o3bo$o2bo$3o!",
+    },
+];
+
+pub fn list(_opts: PatternsOptions) {
+    for entry in LIBRARY {
+        let pattern = entry.pattern();
+        println!("{}, {}x{}", entry, pattern.width, pattern.height);
+    }
+}
+
+pub fn find(name: &str) -> Option<&'static Entry> {
+    LIBRARY
+        .iter()
+        .find(|entry| entry.name.eq_ignore_ascii_case(name))
+}
+
+impl Entry {
+    pub fn pattern(&self) -> Pattern {
+        let rle = format!("x = 0, y = 0, rule = {}\n{}", self.rule, self.rle);
+        Pattern::from_rle(&rle).unwrap_or_else(|e| panic!("Bad {} pattern: {}", self.name, e))
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, {}", self.name, self.kind)?;
+        if let Some(period) = self.period {
+            write!(f, ", period {}", period)?;
+        }
+        if let Some(speed) = self.speed {
+            write!(f, ", {}", speed)?;
+        }
+        write!(f, ", {}", self.rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::hashlife::HashLife;
+    use crate::rule::Rule;
+
+    // Live cells of a pattern run on an unbounded plane
+    struct Run {
+        life: HashLife,
+        // Window the cells are looked for in, all of them stay inside
+        window: (i64, i64, usize, usize),
+    }
+
+    impl Run {
+        fn new(entry: &Entry) -> Self {
+            let rule = entry.rule.parse::<Rule>().unwrap();
+            let pattern = entry.pattern();
+            let life =
+                HashLife::from_cells(&rule, pattern.width, pattern.height, &pattern.cells).unwrap();
+
+            let reach = entry.period.unwrap_or(1) as i64 + 16;
+            let size = |side: usize| side + 2 * reach as usize;
+            let window = (-reach, -reach, size(pattern.width), size(pattern.height));
+            Run { life, window }
+        }
+
+        fn alive(&self) -> HashSet<(i64, i64)> {
+            let (x, y, width, height) = self.window;
+            let cells = self.life.to_cells(x, y, width, height);
+            assert_eq!(
+                self.life.population(),
+                cells.iter().map(|&c| c as u64).sum()
+            );
+
+            (0..width * height)
+                .filter(|&i| cells[i] == 1)
+                .map(|i| (x + (i % width) as i64, y + (i / width) as i64))
+                .collect()
+        }
+    }
+
+    fn shifted(cells: &HashSet<(i64, i64)>, (dx, dy): (i64, i64)) -> HashSet<(i64, i64)> {
+        cells.iter().map(|&(x, y)| (x + dx, y + dy)).collect()
+    }
+
+    // Moved to its top left corner, to compare shapes wherever they are
+    fn shape(cells: &HashSet<(i64, i64)>) -> HashSet<(i64, i64)> {
+        let left = cells.iter().map(|&(x, _)| x).min().unwrap_or(0);
+        let top = cells.iter().map(|&(_, y)| y).min().unwrap_or(0);
+        shifted(cells, (-left, -top))
+    }
+
+    // Every way to move `speed` times `period` cells, straight or diagonally
+    fn moves(entry: &Entry) -> Vec<(i64, i64)> {
+        let Some(speed) = entry.speed else {
+            return vec![(0, 0)];
+        };
+        let (cells, generations) = speed.split_once("c/").unwrap();
+        let cells = if cells.is_empty() {
+            1
+        } else {
+            cells.parse::<u32>().unwrap()
+        };
+        let generations = generations.parse::<u32>().unwrap();
+
+        let period = entry.period.unwrap();
+        assert_eq!(period * cells % generations, 0, "{}", entry.name);
+        let d = (period * cells / generations) as i64;
+        vec![
+            (d, 0),
+            (-d, 0),
+            (0, d),
+            (0, -d),
+            (d, d),
+            (d, -d),
+            (-d, d),
+            (-d, -d),
+        ]
+    }
+
+    #[test]
+    fn patterns_parse_under_their_rules() {
+        for entry in LIBRARY {
+            let rule = entry.rule.parse::<Rule>().unwrap();
+            let pattern = entry.pattern();
+            assert_eq!(pattern.rule, Some(rule), "{}", entry.name);
+            assert!(pattern.cells.contains(&1), "{}", entry.name);
+            // Names are what find goes by
+            let same_name = LIBRARY.iter().filter(|e| e.name == entry.name).count();
+            assert_eq!(same_name, 1, "{}", entry.name);
+        }
+    }
+
+    #[test]
+    fn periods_and_speeds() {
+        for entry in LIBRARY {
+            let mut run = Run::new(entry);
+            let start = run.alive();
+
+            let Some(period) = entry.period else {
+                if entry.kind == "still life" {
+                    run.life.step(1);
+                    assert!(run.alive() == start, "{}", entry.name);
+                }
+                continue;
+            };
+
+            match entry.kind {
+                // The same cells come back moved, and not any sooner
+                "oscillator" | "spaceship" => {
+                    for generation in 1..period {
+                        run.life.step(1);
+                        assert!(
+                            shape(&run.alive()) != shape(&start),
+                            "{} repeats after {}",
+                            entry.name,
+                            generation
+                        );
+                    }
+                    run.life.step(1);
+                    let end = run.alive();
+                    assert!(
+                        moves(entry).iter().any(|&by| shifted(&start, by) == end),
+                        "{} didn't come back after {}",
+                        entry.name,
+                        period
+                    );
+                }
+                // Like them, but leaving gliders or debris behind
+                "gun" | "puffer" => {
+                    run.life.step(period as u64);
+                    let end = run.alive();
+                    assert!(end.len() > start.len(), "{}", entry.name);
+                    assert!(
+                        moves(entry)
+                            .iter()
+                            .any(|&by| shifted(&start, by).is_subset(&end)),
+                        "{} didn't come back after {}",
+                        entry.name,
+                        period
+                    );
+                }
+                // Copies of itself somewhere else
+                "replicator" => {
+                    run.life.step(period as u64);
+                    let end = run.alive();
+                    let (x, y, width, height) = run.window;
+                    let copies = (x..x + width as i64)
+                        .flat_map(|dx| (y..y + height as i64).map(move |dy| (dx, dy)))
+                        .filter(|&by| shifted(&start, by).is_subset(&end))
+                        .count();
+                    assert!(copies >= 2, "{} made {} copies", entry.name, copies);
+                }
+                kind => panic!("{} of kind {} has a period", entry.name, kind),
+            }
+        }
+    }
+}
@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+
+use crate::pattern::{parse_rule, Pattern, MAX_CELLS};
+
+// Golly's Macrocell format, a quadtree written bottom up with every distinct
+// node once, which keeps big patterns that repeat themselves small:
+//
+//   [M2] (golly 4.0)
+//   #R B3/S23
+//   .*$..*$***$
+//   4 1 0 0 0
+//
+// Two state patterns start from 8x8 leaves of . and * rows ending in $,
+// multistate ones from "1 nw ne sw se" nodes of 2x2 states. Every other line
+// is "level nw ne sw se" with the line numbers of the quadrants, counting from
+// 1, and 0 for empty ones. The last node is the root, centred on the origin.
+//
+// Patterns are read into a flat grid like those of every other format, so the
+// box around the live cells can't have more than MAX_CELLS cells in it, even
+// when the file itself is small
+
+// Square of 2^level cells. Level 0 nodes are single cells, with their state as
+// the id
+#[derive(Clone, Copy)]
+struct Node {
+    level: u8,
+    // Quadrants in the order nw, ne, sw, se
+    children: [u32; 4],
+    population: u64,
+}
+
+struct Tree {
+    nodes: Vec<Node>,
+    // The empty node of each level
+    empty: Vec<u32>,
+}
+
+impl Tree {
+    fn new() -> Self {
+        let nodes = (0..=255)
+            .map(|state| Node {
+                level: 0,
+                children: [0; 4],
+                population: (state != 0) as u64,
+            })
+            .collect();
+
+        Tree {
+            nodes,
+            empty: vec![0],
+        }
+    }
+
+    fn join(&mut self, children: [u32; 4]) -> u32 {
+        let level = self.nodes[children[0] as usize].level + 1;
+        // A file can nest full nodes deep enough to have more than 2^64
+        // cells. Only whether there are any counts, the size is checked later
+        let population = children
+            .iter()
+            .map(|&child| self.nodes[child as usize].population)
+            .fold(0, u64::saturating_add);
+
+        self.nodes.push(Node {
+            level,
+            children,
+            population,
+        });
+        self.nodes.len() as u32 - 1
+    }
+
+    fn empty(&mut self, level: u8) -> u32 {
+        while self.empty.len() <= level as usize {
+            let empty = *self.empty.last().unwrap();
+            let bigger = self.join([empty; 4]);
+            self.empty.push(bigger);
+        }
+
+        self.empty[level as usize]
+    }
+
+    // Node of 2^level cells from a square of states, rows going down
+    fn build(&mut self, level: u8, (x, y): (usize, usize), size: usize, cells: &[u8]) -> u32 {
+        if level == 0 {
+            return cells[y * size + x] as u32;
+        }
+
+        let half = 1 << (level - 1);
+        let mut children = [0; 4];
+        for (i, child) in children.iter_mut().enumerate() {
+            let corner = (x + i % 2 * half, y + i / 2 * half);
+            *child = self.build(level - 1, corner, size, cells);
+        }
+        self.join(children)
+    }
+
+    // Box around the cells that aren't dead, from the top left corner of the
+    // node, as left, top, right and bottom
+    fn bounds(&self, id: u32, memo: &mut HashMap<u32, Option<[i64; 4]>>) -> Option<[i64; 4]> {
+        let node = self.nodes[id as usize];
+        if node.population == 0 {
+            return None;
+        }
+        if node.level == 0 {
+            return Some([0, 0, 0, 0]);
+        }
+        if let Some(&bounds) = memo.get(&id) {
+            return bounds;
+        }
+
+        let half = 1i64 << (node.level - 1);
+        let bounds = node
+            .children
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &child)| {
+                let (dx, dy) = (i as i64 % 2 * half, i as i64 / 2 * half);
+                let [left, top, right, bottom] = self.bounds(child, memo)?;
+                Some([left + dx, top + dy, right + dx, bottom + dy])
+            })
+            .reduce(|a, b| {
+                [
+                    a[0].min(b[0]),
+                    a[1].min(b[1]),
+                    a[2].max(b[2]),
+                    a[3].max(b[3]),
+                ]
+            });
+
+        memo.insert(id, bounds);
+        bounds
+    }
+
+    // Writes the cells of a node with its top left corner at (x, y) of a grid
+    fn fill(&self, id: u32, (x, y): (i64, i64), width: usize, cells: &mut [u8]) {
+        let node = self.nodes[id as usize];
+        if node.population == 0 {
+            return;
+        }
+        if node.level == 0 {
+            cells[y as usize * width + x as usize] = id as u8;
+            return;
+        }
+
+        let half = 1i64 << (node.level - 1);
+        for (i, &child) in node.children.iter().enumerate() {
+            let corner = (x + i as i64 % 2 * half, y + i as i64 / 2 * half);
+            self.fill(child, corner, width, cells);
+        }
+    }
+}
+
+impl Pattern {
+    pub fn from_macrocell(text: &str) -> Result<Pattern, String> {
+        let mut tree = Tree::new();
+        let mut rule = None;
+        // Node of every line, line 0 being the empty node of any level
+        let mut lines = vec![None];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                if let Some(r) = comment.strip_prefix('R') {
+                    rule = Some(parse_rule(r.trim()).map_err(error)?);
+                }
+                continue;
+            }
+
+            let id = if line.starts_with(['.', '*', '$']) {
+                tree.leaf(line).map_err(error)?
+            } else {
+                tree.node(line, &lines).map_err(error)?
+            };
+            lines.push(Some(id));
+        }
+
+        let root = lines.last().copied().flatten().ok_or("pattern is empty")?;
+        let [left, top, right, bottom] = tree
+            .bounds(root, &mut HashMap::new())
+            .ok_or("pattern is empty")?;
+
+        let (width, height) = ((right - left) as usize + 1, (bottom - top) as usize + 1);
+        if width.saturating_mul(height) > MAX_CELLS {
+            return Err(format!(
+                "{}x{} pattern is too big, Macrocell files are read into a grid of at most {} cells",
+                width, height, MAX_CELLS
+            ));
+        }
+
+        let mut cells = vec![0; width * height];
+        tree.fill(root, (-left, -top), width, &mut cells);
+
+        // The origin is in the middle of the root, wherever the cells are
+        let half = 1i64 << (tree.nodes[root as usize].level - 1);
+
+        Ok(Pattern {
+            width,
+            height,
+            cells,
+            rule,
+            origin: Some((half - left, half - top)),
+        })
+    }
+
+    // Writes the pattern as Macrocell, with its origin or middle at the
+    // origin of the file
+    pub fn to_macrocell(&self) -> String {
+        let multistate = self.cells.iter().any(|&cell| cell > 1)
+            || self.rule.is_some_and(|rule| rule.states > 2);
+
+        let (origin_x, origin_y) = self
+            .origin
+            .unwrap_or(((self.width / 2) as i64, (self.height / 2) as i64));
+        // The origin can be outside of the cells
+        let reach = origin_x
+            .max(origin_y)
+            .max(self.width as i64 - origin_x)
+            .max(self.height as i64 - origin_y) as u64;
+        // Two state leaves are 8x8 already
+        let level = (reach * 2).next_power_of_two().trailing_zeros().max(4) as u8;
+        let half = 1i64 << (level - 1);
+
+        let mut writer = Writer {
+            pattern: self,
+            multistate,
+            // Root corner in pattern coordinates
+            offset: (origin_x - half, origin_y - half),
+            lines: Vec::new(),
+            leaves: HashMap::new(),
+            nodes: HashMap::new(),
+        };
+        if writer.write(level, (0, 0)) == 0 {
+            // Nothing alive, the root still has to be there
+            writer.lines.push(format!("{} 0 0 0 0", level));
+        }
+
+        let mut text = "[M2] (wallpaper_of_life)\n".to_string();
+        if let Some(rule) = &self.rule {
+            text += &format!("#R {}\n", rule);
+        }
+        for line in writer.lines {
+            text += &line;
+            text.push('\n');
+        }
+        text
+    }
+}
+
+impl Tree {
+    // 8x8 cells, rows ending in $ and the ones left out at the end dead
+    fn leaf(&mut self, line: &str) -> Result<u32, String> {
+        let mut cells = [0; 64];
+        let (mut x, mut y) = (0, 0);
+
+        for c in line.chars() {
+            match c {
+                '$' => (x, y) = (0, y + 1),
+                '.' | '*' if x < 8 && y < 8 => {
+                    cells[y * 8 + x] = (c == '*') as u8;
+                    x += 1;
+                }
+                '.' | '*' => return Err("leaf is bigger than 8x8".to_string()),
+                c => return Err(format!("unexpected '{}'", c)),
+            }
+        }
+
+        Ok(self.build(3, (0, 0), 8, &cells))
+    }
+
+    fn node(&mut self, line: &str, lines: &[Option<u32>]) -> Result<u32, String> {
+        let numbers = line
+            .split_whitespace()
+            .map(|n| {
+                n.parse::<usize>()
+                    .map_err(|_| format!("expected a number, found '{}'", n))
+            })
+            .collect::<Result<Vec<usize>, String>>()?;
+        let [level, nw, ne, sw, se] = numbers[..] else {
+            return Err(format!("expected 5 numbers, found {}", numbers.len()));
+        };
+        if !(1..64).contains(&level) {
+            return Err(format!("level {} is out of range", level));
+        }
+
+        let mut children = [0; 4];
+        for (child, quadrant) in children.iter_mut().zip([nw, ne, sw, se]) {
+            *child = if level == 1 {
+                // Multistate leaves give the states themselves
+                if quadrant > 255 {
+                    return Err(format!("state {} is too high", quadrant));
+                }
+                quadrant as u32
+            } else if quadrant == 0 {
+                self.empty(level as u8 - 1)
+            } else {
+                let id = lines
+                    .get(quadrant)
+                    .copied()
+                    .flatten()
+                    .ok_or_else(|| format!("node {} isn't defined yet", quadrant))?;
+                if self.nodes[id as usize].level as usize != level - 1 {
+                    return Err(format!("node {} isn't of level {}", quadrant, level - 1));
+                }
+                id
+            };
+        }
+
+        Ok(self.join(children))
+    }
+}
+
+// Builds the lines of a file from the pattern, sharing identical nodes
+struct Writer<'a> {
+    pattern: &'a Pattern,
+    multistate: bool,
+    offset: (i64, i64),
+    lines: Vec<String>,
+    leaves: HashMap<String, usize>,
+    nodes: HashMap<(u8, [usize; 4]), usize>,
+}
+
+impl Writer<'_> {
+    fn cell(&self, x: i64, y: i64) -> u8 {
+        let (x, y) = (x + self.offset.0, y + self.offset.1);
+        let pattern = self.pattern;
+        if x < 0 || y < 0 || x >= pattern.width as i64 || y >= pattern.height as i64 {
+            return 0;
+        }
+        pattern.cells[y as usize * pattern.width + x as usize]
+    }
+
+    // Line number of the node at (x, y) from the root corner, 0 when empty
+    fn write(&mut self, level: u8, (x, y): (i64, i64)) -> usize {
+        let size = 1i64 << level;
+        let (left, top) = (x + self.offset.0, y + self.offset.1);
+        if left >= self.pattern.width as i64
+            || top >= self.pattern.height as i64
+            || left + size <= 0
+            || top + size <= 0
+        {
+            return 0;
+        }
+
+        if level == 3 && !self.multistate {
+            let mut rows = (0..8)
+                .map(|dy| {
+                    let row = (0..8)
+                        .map(|dx| {
+                            if self.cell(x + dx, y + dy) != 0 {
+                                '*'
+                            } else {
+                                '.'
+                            }
+                        })
+                        .collect::<String>();
+                    row.trim_end_matches('.').to_string() + "$"
+                })
+                .collect::<String>();
+            while rows.ends_with("$$") {
+                rows.pop();
+            }
+            if rows == "$" {
+                return 0;
+            }
+
+            let next = self.lines.len() + 1;
+            return *self.leaves.entry(rows.clone()).or_insert_with(|| {
+                self.lines.push(rows);
+                next
+            });
+        }
+
+        let children = if level == 1 {
+            [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| self.cell(x + dx, y + dy) as usize)
+        } else {
+            let half = size / 2;
+            [(0, 0), (half, 0), (0, half), (half, half)]
+                .map(|(dx, dy)| self.write(level - 1, (x + dx, y + dy)))
+        };
+        if children == [0; 4] {
+            return 0;
+        }
+
+        if let Some(&line) = self.nodes.get(&(level, children)) {
+            return line;
+        }
+        let [nw, ne, sw, se] = children;
+        self.lines
+            .push(format!("{} {} {} {} {}", level, nw, ne, sw, se));
+        self.nodes.insert((level, children), self.lines.len());
+        self.lines.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(pattern: &Pattern) -> Pattern {
+        Pattern::from_macrocell(&pattern.to_macrocell()).unwrap()
+    }
+
+    #[test]
+    fn reads_two_states() {
+        let text = "[M2] (golly 4.0)\n#R B3/S23\n.*$..*$***$\n4 1 0 0 0\n";
+        let pattern = Pattern::from_macrocell(text).unwrap();
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        assert_eq!(pattern.cells, [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(pattern.rule, Some("B3/S23".parse().unwrap()));
+        // The leaf is the north west quadrant of a 16x16 root
+        assert_eq!(pattern.origin, Some((8, 8)));
+    }
+
+    #[test]
+    fn glider_round_trips() {
+        let glider = Pattern::from_rle("x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!").unwrap();
+        let text = glider.to_macrocell();
+        assert!(text.starts_with("[M2]"));
+        assert!(text.contains("#R B3/S23\n"));
+
+        let read = round_trip(&glider);
+        assert_eq!(read.cells, glider.cells);
+        assert_eq!(read.rule, glider.rule);
+        assert_eq!(round_trip(&read), read);
+    }
+
+    #[test]
+    fn multistate_round_trips() {
+        let pattern = Pattern::from_rle("x = 4, y = 2, rule = 23/3/4\nA.BC$2.pA!").unwrap();
+        let read = round_trip(&pattern);
+        assert_eq!(read.cells, pattern.cells);
+        assert_eq!(read.rule, pattern.rule);
+        assert_eq!(round_trip(&read), read);
+    }
+
+    #[test]
+    fn far_off_centre_round_trips() {
+        let pattern = Pattern::from_life_106("#Life 1.06\n1000 -700\n1001 -700\n").unwrap();
+        assert_eq!(pattern.origin, Some((-1000, 700)));
+
+        let read = round_trip(&pattern);
+        assert_eq!(read, pattern);
+        assert_eq!(round_trip(&read), pattern);
+    }
+
+    #[test]
+    fn board_round_trips() {
+        let mut state = vec![0; 16 * 8];
+        state[2 * 16 + 13] = 1;
+        state[3 * 16 + 14] = 1;
+        state[4 * 16 + 12..4 * 16 + 15].fill(1);
+        let rule = "B36/S23".parse().unwrap();
+        let pattern = Pattern::from_board(16, 8, &state, rule).unwrap();
+        assert_eq!(round_trip(&pattern), pattern);
+    }
+
+    #[test]
+    fn empty_pattern() {
+        let text = Pattern::from_rle("x = 2, y = 2\n!").unwrap().to_macrocell();
+        assert_eq!(
+            Pattern::from_macrocell(&text).unwrap_err(),
+            "pattern is empty"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = Pattern::from_macrocell("[M2]\n4 2 0 0 0\n").unwrap_err();
+        assert_eq!(error, "line 2: node 2 isn't defined yet");
+        let error = Pattern::from_macrocell("[M2]\n.*$\n5 1 0 0 0\n").unwrap_err();
+        assert_eq!(error, "line 3: node 1 isn't of level 4");
+        let error = Pattern::from_macrocell("[M2]\n.*.*.*.*.\n").unwrap_err();
+        assert_eq!(error, "line 2: leaf is bigger than 8x8");
+        let error = Pattern::from_macrocell("[M2]\n1 300 0 0 0\n").unwrap_err();
+        assert_eq!(error, "line 2: state 300 is too high");
+    }
+
+    #[test]
+    fn too_big() {
+        // Two cells at opposite corners of a 2^20 square, a small file
+        let mut text = "[M2]\n*$\n".to_string() + &"$".repeat(7) + ".......*$\n";
+        let (mut nw, mut se) = (1, 2);
+        for level in 4..20 {
+            text += &format!("{} {} 0 0 0\n{} 0 0 0 {}\n", level, nw, level, se);
+            (nw, se) = (se + 1, se + 2);
+        }
+        text += &format!("20 {} 0 0 {}\n", nw, se);
+        let error = Pattern::from_macrocell(&text).unwrap_err();
+        assert!(
+            error.starts_with("1048576x1048576 pattern is too big"),
+            "{}",
+            error
+        );
+
+        // Full all the way down, more cells than the population can count
+        let mut text = "[M2]\n".to_string() + &"********$".repeat(8) + "\n";
+        for level in 4..64 {
+            text += &format!(
+                "{} {} {} {} {}\n",
+                level,
+                level - 3,
+                level - 3,
+                level - 3,
+                level - 3
+            );
+        }
+        let error = Pattern::from_macrocell(&text).unwrap_err();
+        assert!(
+            error.starts_with("9223372036854775808x9223372036854775808 pattern is too big"),
+            "{}",
+            error
+        );
+    }
+}
@@ -4,23 +4,52 @@ extern crate gumdrop;
 extern crate x11_dl;
 extern crate x11rb;
 
+mod bench;
+mod game_of_life;
+mod hashlife;
+mod image;
+mod library;
+mod macrocell;
+mod pattern;
+mod render;
+mod rule;
+mod simulator;
+mod software;
+mod terminal;
+mod verify;
+
+use bench::BenchOptions;
+use game_of_life::Tiles;
 use gumdrop::Options;
+use hashlife::HashLifeBoard;
+use library::PatternsOptions;
+use pattern::{Pattern, Symmetry};
+use render::{Look, RenderOptions};
+use rule::{Ltl, Rule, RuleKind, Topology};
+use simulator::{Engine, Region, Simulator};
+use software::SoftwareWallpaper;
+use terminal::{Glyphs, TerminalWallpaper};
+use verify::VerifyOptions;
 
 use glfw::{
-    Action, Context, Key, Modifiers, MouseButton, OpenGlProfileHint, Window, WindowEvent,
-    WindowHint,
+    Action, Context, ContextCreationApi, Key, Modifiers, MouseButton, OpenGlProfileHint, Window,
+    WindowEvent, WindowHint,
 };
 // include the OpenGL type aliases
 use gl::types::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 use std::ffi::{c_void, CStr, CString};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use x11rb::connection::Connection;
 use x11rb::protocol::xproto::{
     ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt as XprotoConnectionExt, StackMode,
 };
 use x11rb::wrapper::ConnectionExt;
 
-use std::ptr::null;
+use std::path::{Path, PathBuf};
+use std::ptr::{null, null_mut};
 use std::str::FromStr;
 
 #[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
@@ -39,39 +68,99 @@ impl FromStr for Color {
             }
 
             Ok(Color(
-                ((c >> 24) as u8 & 0xFF),
-                ((c >> 16) as u8 & 0xFF),
-                ((c >> 8) as u8 & 0xFF),
-                (c as u8 & 0xFF),
+                (c >> 24) as u8,
+                (c >> 16) as u8,
+                (c >> 8) as u8,
+                c as u8,
             ))
         }
     }
 }
 
 impl Color {
-    fn r(&self) -> f32 {
-        self.0 as f32 / 255.0
+    fn lerp(&self, other: Color, t: f32) -> Color {
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color(
+            mix(self.0, other.0),
+            mix(self.1, other.1),
+            mix(self.2, other.2),
+            mix(self.3, other.3),
+        )
     }
+}
+
+// Colors that the dying states of a Generations rule fade through
+#[derive(Debug, Clone, Default)]
+struct Gradient(Vec<Color>);
+
+impl FromStr for Gradient {
+    type Err = &'static str;
 
-    fn g(&self) -> f32 {
-        self.1 as f32 / 255.0
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(',')
+            .map(|c| c.trim().parse())
+            .collect::<Result<Vec<Color>, _>>()
+            .map(Gradient)
     }
+}
+
+impl Gradient {
+    fn sample(&self, t: f32) -> Color {
+        let stops = &self.0;
+        if stops.len() == 1 {
+            return stops[0];
+        }
 
-    fn b(&self) -> f32 {
-        self.2 as f32 / 255.0
+        let pos = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let i = (pos.floor() as usize).min(stops.len() - 2);
+        stops[i].lerp(stops[i + 1], pos - i as f32)
     }
+}
+
+// Colors that the states of any rule are drawn with
+#[derive(Debug, Clone)]
+struct Colors {
+    live: Color,
+    dead: Color,
+    gradient: Gradient,
+}
+
+impl Colors {
+    // Color for every state, indexed by the value stored in the state texture
+    fn palette(&self, states: u32) -> Vec<Color> {
+        let dying = states - 2;
+        let mut palette = vec![self.dead, self.live];
+
+        for i in 0..dying {
+            let color = if self.gradient.0.is_empty() {
+                // Fade from live to dead, without reaching either
+                self.live
+                    .lerp(self.dead, (i + 1) as f32 / (dying + 1) as f32)
+            } else if dying == 1 {
+                self.gradient.sample(0.0)
+            } else {
+                self.gradient.sample(i as f32 / (dying - 1) as f32)
+            };
+            palette.push(color);
+        }
 
-    fn a(&self) -> f32 {
-        self.3 as f32 / 255.0
+        palette
     }
 }
 
+// Cell states are stored in the red channel of the state texture
+const DEAD: u32 = 0xFF000000;
+
 // Define options for the program.
 #[derive(Debug, Options)]
 struct WolOptions {
     #[options(help = "print help message")]
     help: bool,
 
+    #[options(command)]
+    command: Option<Command>,
+
     #[options(help = "Simulation cell size in pixels", default = "4", no_short)]
     pixels: u32,
 
@@ -82,14 +171,89 @@ struct WolOptions {
     )]
     fps: f64,
 
-    #[options(help = "Rule for any life-like automata", default = "B3/S23", no_short)]
-    rule: String,
+    #[options(
+        help = "Rule for any life-like automata, e.g. B3/S23, 23/3, B2/S/C3, B2n3/S23-q or B2/S34H. Defaults to the pattern's rule, or B3/S23",
+        parse(try_from_str),
+        no_short
+    )]
+    rule: Option<Rule>,
+
+    #[options(
+        help = "Pattern to start with in the middle of the board, and to stamp with middle click. A file (RLE, .cells, Life 1.05 and 1.06 or Macrocell) or a name from the patterns command",
+        no_short
+    )]
+    pattern: Option<String>,
+
+    #[options(
+        help = "Pattern to put on the board with its top left corner on a cell, as NAME@X,Y with a file or library name. NAME@X,Y,TRANSFORM turns it first, with ident, rcw, rccw, flip, flip_x, flip_y, swap_xy or swap_xy_flip. Can be given more than once",
+        parse(try_from_str),
+        no_short
+    )]
+    stamp: Vec<Placement>,
+
+    #[options(help = "Directory that S saves the board to", default = ".", no_short)]
+    save_dir: PathBuf,
+
+    #[options(
+        help = "Format S saves the board in: rle, or mc (Macrocell) for big boards",
+        default = "rle",
+        parse(try_from_str),
+        no_short
+    )]
+    save_format: SaveFormat,
+
+    #[options(
+        help = "Another rule to switch to with Tab, can be given more than once",
+        parse(try_from_str),
+        no_short
+    )]
+    next_rule: Vec<Rule>,
+
+    #[options(
+        help = "Edges of the board: T (torus), P (plane), K (Klein bottle) or C (cross-surface), overrides the rule suffix",
+        parse(try_from_str),
+        no_short
+    )]
+    topology: Option<Topology>,
+
+    #[options(
+        help = "What runs the simulation: gpu, basic, packed, threaded or hashlife",
+        default = "gpu",
+        parse(try_from_str),
+        no_short
+    )]
+    engine: Engine,
+
+    #[options(
+        help = "How the wallpaper is drawn: gl, x11 without OpenGL, or terminal without X",
+        default = "gl",
+        parse(try_from_str),
+        no_short
+    )]
+    output: Output,
+
+    #[options(
+        help = "Seed for everything random, printed at startup so that a session can be replayed. Random by default",
+        no_short
+    )]
+    seed: Option<u64>,
 
-    #[options(help = "Wallpaper width in pixels, defaults to screen width", no_short)]
+    #[options(
+        help = "Characters the terminal output draws cells with: half (1x2 cells) or braille (2x4 cells)",
+        default = "half",
+        parse(try_from_str),
+        no_short
+    )]
+    glyphs: Glyphs,
+
+    #[options(
+        help = "Wallpaper width in pixels, defaults to screen width or 1920 when rendering",
+        no_short
+    )]
     width: Option<u32>,
 
     #[options(
-        help = "Wallpaper height in pixels, defaults to screen height",
+        help = "Wallpaper height in pixels, defaults to screen height or 1080 when rendering",
         no_short
     )]
     height: Option<u32>,
@@ -109,62 +273,287 @@ struct WolOptions {
         no_short
     )]
     dead: Color,
+
+    #[options(
+        help = "Comma separated colors that dying cells fade through in Generations rules",
+        parse(try_from_str),
+        no_short
+    )]
+    gradient: Option<Gradient>,
+}
+
+// Tools that run instead of the wallpaper
+#[derive(Debug, Options)]
+enum Command {
+    #[options(help = "Check the GPU rules against BasicGoL, offscreen")]
+    Verify(VerifyOptions),
+    #[options(help = "Time the engines on a random board")]
+    Bench(BenchOptions),
+    #[options(help = "Write frames of a random board to images, offscreen")]
+    Render(RenderOptions),
+    #[options(help = "List the patterns that come with the wallpaper")]
+    Patterns(PatternsOptions),
+}
+
+// A pattern put somewhere with --stamp
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Placement {
+    name: String,
+    x: usize,
+    y: usize,
+    symmetry: Symmetry,
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let format = "Stamp must be in format NAME@X,Y or NAME@X,Y,TRANSFORM";
+        let (name, at) = value.rsplit_once('@').ok_or(format)?;
+
+        let mut parts = at.split(',');
+        let (Some(x), Some(y), symmetry, None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format.to_string());
+        };
+        let parse = |n: &str| {
+            n.trim()
+                .parse()
+                .map_err(|_| "Stamp position must be two numbers".to_string())
+        };
+
+        Ok(Placement {
+            name: name.to_string(),
+            x: parse(x)?,
+            y: parse(y)?,
+            symmetry: symmetry.map_or(Ok(Symmetry::Identity), |s| s.trim().parse())?,
+        })
+    }
+}
+
+// Board size given as WIDTHxHEIGHT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Size {
+    width: usize,
+    height: usize,
+}
+
+impl FromStr for Size {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (width, height) = value
+            .split_once('x')
+            .ok_or("Size must be in format WIDTHxHEIGHT")?;
+        let parse = |n: &str| match n.parse() {
+            Ok(0) | Err(_) => Err("Size must be two positive numbers"),
+            Ok(n) => Ok(n),
+        };
+
+        Ok(Size {
+            width: parse(width)?,
+            height: parse(height)?,
+        })
+    }
 }
 
 fn main() {
-    let opts: WolOptions = WolOptions::parse_args_default_or_exit();
+    let mut opts: WolOptions = WolOptions::parse_args_default_or_exit();
+
+    let render = match opts.command.take() {
+        Some(Command::Verify(opts)) => {
+            if !verify::verify(opts) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Bench(opts)) => return bench::bench(opts),
+        Some(Command::Render(opts)) => Some(opts),
+        Some(Command::Patterns(opts)) => return library::list(opts),
+        None => None,
+    };
 
     println!("{:#?}", opts);
-    // return;
-    // let rule = "B45678/S01234";
-    // let rule = "B34/S234567";
-    // let rule = "B34/S012345678";
-    // let rule = "B3/S23";
 
-    let (born, survive) = parse_rule_to_cond(&opts.rule).unwrap();
+    let pattern = opts.pattern.as_ref().map(|name| {
+        find_pattern(name).unwrap_or_else(|e| panic!("Couldn't load the pattern: {}", e))
+    });
+    let stamps = opts
+        .stamp
+        .iter()
+        .map(|placement| {
+            let pattern = find_pattern(&placement.name)
+                .unwrap_or_else(|e| panic!("Couldn't load the stamp: {}", e));
+            (
+                pattern.transformed(placement.symmetry),
+                (placement.x, placement.y),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let rule = opts
+        .rule
+        .or_else(|| pattern.as_ref().and_then(|p| p.rule))
+        .or_else(|| stamps.first().and_then(|(p, _)| p.rule))
+        .unwrap_or_else(|| "B3/S23".parse().unwrap());
+    let mut rules = vec![rule];
+    rules.extend(opts.next_rule);
+    if let Some(topology) = opts.topology {
+        for rule in &mut rules {
+            rule.topology = topology;
+        }
+    }
+
+    let seed = opts.seed.unwrap_or_else(rand::random);
+    println!("Seed: {}", seed);
+
+    let colors = Colors {
+        live: opts.live,
+        dead: opts.dead,
+        gradient: opts.gradient.unwrap_or_default(),
+    };
+
+    let start = Start { pattern, stamps };
+
+    if let Some(render) = render {
+        let look = Look {
+            size: Size {
+                width: opts.width.unwrap_or(1920) as usize,
+                height: opts.height.unwrap_or(1080) as usize,
+            },
+            scale: opts.pixels,
+            rule: rules[0],
+            colors,
+            engine: opts.engine,
+            seed,
+            start,
+        };
+        if let Err(e) = render::render(render, look) {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let save = Saving {
+        dir: opts.save_dir,
+        format: opts.save_format,
+    };
+
+    if opts.output == Output::Terminal {
+        let mut terminal = TerminalWallpaper::new(
+            1.0 / opts.fps,
+            rules,
+            colors,
+            opts.engine,
+            seed,
+            start,
+            opts.glyphs,
+            save,
+        )
+        .unwrap_or_else(|e| panic!("Couldn't run in the terminal: {}", e));
+        return terminal.main_loop();
+    }
+
+    if opts.output == Output::Gl {
+        match WoL::new(
+            opts.pixels,
+            1.0 / opts.fps,
+            rules.clone(),
+            colors.clone(),
+            opts.engine,
+            seed,
+            start.clone(),
+            save.clone(),
+        ) {
+            Ok(mut wol) => return wol.main_loop(),
+            Err(GlError::Context(e)) => println!("{}, drawing without OpenGL", e),
+            Err(GlError::Engine(e)) => panic!("Couldn't draw the wallpaper: {}", e),
+        }
+    }
 
-    let mut wol = WoL::new(
+    let mut software = SoftwareWallpaper::new(
         opts.pixels,
         1.0 / opts.fps,
-        &born,
-        &survive,
-        opts.live,
-        opts.dead,
-    );
-    wol.main_loop();
+        rules,
+        colors,
+        opts.engine,
+        seed,
+        start,
+        save,
+    )
+    .unwrap_or_else(|e| panic!("Couldn't draw the wallpaper: {}", e));
+    software.main_loop();
+}
+
+// Where and how S saves the board
+#[derive(Debug, Clone)]
+struct Saving {
+    dir: PathBuf,
+    format: SaveFormat,
 }
 
-fn parse_rule_to_cond(rule: &str) -> Option<(String, String)> {
-    let b_index = rule.find('B')?;
-    let div_s_index = rule.find("/S")?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SaveFormat {
+    Rle,
+    Macrocell,
+}
 
-    let born_cond = digits_to_cond(&rule[b_index + 1..div_s_index])?;
-    let survive_cond = digits_to_cond(&rule[div_s_index + 2..])?;
+impl FromStr for SaveFormat {
+    type Err = String;
 
-    Some((born_cond, survive_cond))
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "rle" => Ok(SaveFormat::Rle),
+            "mc" | "macrocell" => Ok(SaveFormat::Macrocell),
+            _ => Err(format!(
+                "unknown save format '{}', expected rle or mc",
+                value
+            )),
+        }
+    }
 }
 
-fn digits_to_cond(digits: &str) -> Option<String> {
-    if digits.is_empty() {
-        Some("false".into())
-    } else {
-        Some(
-            digits
-                .chars()
-                .map(|n| {
-                    if '0' <= n && n <= '8' {
-                        Some(format!("sum == {}", n))
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Option<Vec<String>>>()?
-                .join(" || "),
-        )
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Gl,
+    X11,
+    Terminal,
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "gl" => Ok(Output::Gl),
+            "x11" => Ok(Output::X11),
+            "terminal" => Ok(Output::Terminal),
+            _ => Err(format!(
+                "unknown output '{}', expected gl, x11 or terminal",
+                value
+            )),
+        }
     }
 }
 
+// Why the OpenGL wallpaper didn't start. Drawing without OpenGL only helps
+// when there is no context
+enum GlError {
+    Context(&'static str),
+    // The engine can't run the rule, which no other output changes
+    Engine(&'static str),
+}
+
 struct WoL {
+    // Fields are dropped in order, these hold GL objects and have to go
+    // before the window takes the context with it
+    gpu: GpuGoL,
+    // Runs the simulation instead of the GPU when set, which then only holds
+    // the state for drawing
+    cpu: Option<Box<dyn Simulator>>,
+
     glfw: glfw::Glfw,
     width: u32,
     height: u32,
@@ -173,6 +562,30 @@ struct WoL {
     window: Window,
     events: std::sync::mpsc::Receiver<(f64, WindowEvent)>,
 
+    copy_shader: GLuint,
+    copy_uni_state: GLint,
+
+    palette_buf: GLuint,
+
+    rule: Rule,
+    // Rules to cycle through with Tab, and which one of them is running
+    rules: Vec<Rule>,
+    rule_index: usize,
+    colors: Colors,
+
+    // Everything random comes from here, seeded with --seed
+    rng: StdRng,
+    // What middle click puts down
+    brush: Brush,
+    // Where S saves the board
+    save: Saving,
+}
+
+// The simulation running in shaders, ping-ponging between two state textures
+struct GpuGoL {
+    width: u32,
+    height: u32,
+
     front_tex: GLenum,
     front_buf: GLuint,
     back_tex: GLenum,
@@ -182,150 +595,378 @@ struct WoL {
 
     gol_shader: GLuint,
     gol_uni_state: GLint,
-    copy_shader: GLuint,
-    copy_uni_state: GLint,
 
+    quad: Quad,
+
+    // Only created once a Larger than Life rule is used
+    ltl: Option<LtlPasses>,
+    dirty: DirtyTiles,
+
+    rule: Rule,
+}
+
+// Two triangles covering the viewport, that every pass draws
+struct Quad {
     vertex_array: GLuint,
     vertex_buffer: GLuint,
 }
 
-impl WoL {
-    fn new(
-        scale: u32,
-        period: f64,
-        born_cond: &str,
-        survive_cond: &str,
-        live_color: Color,
-        dead_color: Color,
-    ) -> WoL {
-        let mut my_glfw = glfw::init(glfw::FAIL_ON_ERRORS.clone()).unwrap();
+// Larger than Life rules count neighbors from per row prefix sums of live
+// cells, these passes build them before the ltl shader runs in place of the
+// gol shader
+struct LtlPasses {
+    prefix_tex: [GLenum; 2],
+    prefix_buf: [GLuint; 2],
+    frame_buffer: GLuint,
+
+    shader: GLuint,
+    uni_state: GLint,
+
+    alive_shader: GLuint,
+    alive_uni_state: GLint,
+    scan_shader: GLuint,
+    scan_uni_prefix: GLint,
+    scan_uni_step: GLint,
+
+    // Scan steps needed to cover the whole width
+    steps: u32,
+}
 
-        let (width, height) = my_glfw.with_primary_monitor(|_g, mon| {
-            let vid = mon.unwrap().get_video_mode().unwrap();
-            (vid.width, vid.height)
-        });
+impl LtlPasses {
+    fn new(quad_vertex: &CStr, width: GLint, height: GLint) -> Self {
+        let ltl_frag = CString::new(include_str!("../glsl/ltl.frag")).unwrap();
+        let alive_frag = CString::new(include_str!("../glsl/ltl_alive.frag")).unwrap();
+        let scan_frag = CString::new(include_str!("../glsl/ltl_scan.frag")).unwrap();
 
-        my_glfw.window_hint(WindowHint::ContextVersionMajor(3));
-        my_glfw.window_hint(WindowHint::ContextVersionMinor(3));
-        my_glfw.window_hint(WindowHint::OpenGlProfile(OpenGlProfileHint::Core));
-        my_glfw.window_hint(WindowHint::TransparentFramebuffer(true));
+        let shader = program_from_sources(quad_vertex, &ltl_frag).unwrap();
+        let alive_shader = program_from_sources(quad_vertex, &alive_frag).unwrap();
+        let scan_shader = program_from_sources(quad_vertex, &scan_frag).unwrap();
 
-        // Create a windowed mode window and its OpenGL context
-        let (mut window, events) = my_glfw
-            .create_window(
-                width,
-                height,
-                "Wallpaper of Life",
-                glfw::WindowMode::Windowed,
-            )
-            .expect("Failed to create GLFW window.");
+        let prefix_tex = [gl::TEXTURE2, gl::TEXTURE3];
+        let prefix_buf = prefix_tex.map(|tex| make_count_texture2d(tex, width, height));
 
+        let mut frame_buffer = 0;
         unsafe {
-            let xlib_xcb = x11_dl::xlib_xcb::Xlib_xcb::open().unwrap();
+            gl::GenFramebuffers(1, &mut frame_buffer);
+        }
 
-            let disp = my_glfw.get_x11_display() as *mut x11_dl::xlib::Display;
-            let win = window.get_x11_window();
+        let steps = (width as u32).next_power_of_two().trailing_zeros();
 
-            /* Get the XCB connection from the display */
-            let xcb_conn = (xlib_xcb.XGetXCBConnection)(disp);
-            if xcb_conn.is_null() {
-                panic!("Can't get xcb connection from display");
+        unsafe {
+            gl::UseProgram(shader);
+            gl::Uniform1i(
+                get_uniform_location(shader, "prefix"),
+                (prefix_tex[steps as usize % 2] - gl::TEXTURE0) as i32,
+            );
+            gl::UseProgram(0);
+        }
+
+        LtlPasses {
+            prefix_tex,
+            prefix_buf,
+            frame_buffer,
+
+            shader,
+            uni_state: get_uniform_location(shader, "state"),
+
+            alive_shader,
+            alive_uni_state: get_uniform_location(alive_shader, "state"),
+            scan_shader,
+            scan_uni_prefix: get_uniform_location(scan_shader, "prefix"),
+            scan_uni_step: get_uniform_location(scan_shader, "step"),
+
+            steps,
+        }
+    }
+
+    // Expects the shader to be in use
+    fn set_rule(&self, ltl: &Ltl, states: u32, topology: Topology) {
+        let range = ltl.range as i32;
+        let half_widths = (-range..=range)
+            .map(|dy| ltl.shape.half_width(ltl.range, dy).unwrap() as GLint)
+            .collect::<Vec<GLint>>();
+
+        unsafe {
+            gl::Uniform1i(get_uniform_location(self.shader, "range"), range);
+            gl::Uniform1iv(
+                get_uniform_location(self.shader, "halfWidth"),
+                half_widths.len() as GLsizei,
+                half_widths.as_ptr(),
+            );
+            gl::Uniform1i(
+                get_uniform_location(self.shader, "middle"),
+                ltl.middle as GLint,
+            );
+            gl::Uniform2i(
+                get_uniform_location(self.shader, "birth"),
+                ltl.birth.0 as GLint,
+                ltl.birth.1 as GLint,
+            );
+            gl::Uniform2i(
+                get_uniform_location(self.shader, "survive"),
+                ltl.survive.0 as GLint,
+                ltl.survive.1 as GLint,
+            );
+            gl::Uniform1i(get_uniform_location(self.shader, "states"), states as GLint);
+            gl::Uniform1i(
+                get_uniform_location(self.shader, "topology"),
+                topology as GLint,
+            );
+        }
+    }
+
+    // Expects the quad's vertex array to be bound
+    fn run(&self, state_tex: GLenum) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.frame_buffer);
+
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                self.prefix_buf[0],
+                0,
+            );
+            gl::UseProgram(self.alive_shader);
+            gl::Uniform1i(self.alive_uni_state, (state_tex - gl::TEXTURE0) as i32);
+            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+
+            gl::UseProgram(self.scan_shader);
+            for step in 0..self.steps {
+                let (from, to) = (step as usize % 2, (step as usize + 1) % 2);
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::TEXTURE_2D,
+                    self.prefix_buf[to],
+                    0,
+                );
+                gl::Uniform1i(
+                    self.scan_uni_prefix,
+                    (self.prefix_tex[from] - gl::TEXTURE0) as i32,
+                );
+                gl::Uniform1i(self.scan_uni_step, 1 << step);
+                gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
             }
 
-            make_window_wallpaper(xcb_conn, win as u32, width, height);
+            gl::UseProgram(0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
         }
+    }
+}
 
-        // Make the window's context current
-        window.make_current();
+impl Drop for LtlPasses {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.frame_buffer);
+            gl::DeleteTextures(2, self.prefix_buf.as_ptr());
+            gl::DeleteProgram(self.shader);
+            gl::DeleteProgram(self.alive_shader);
+            gl::DeleteProgram(self.scan_shader);
+        }
+    }
+}
 
-        // window.set_all_polling(true);
-        window.set_refresh_polling(true);
-        window.set_mouse_button_polling(true);
-        window.set_cursor_pos_polling(true);
-        // window.set_cursor_enter_polling(true);
+// Which tiles of the board changed on the last ticks. The gol and ltl passes
+// only draw over tiles close to a recent change, the rest of the texture being
+// drawn to already holds their state from two ticks ago, which is the same.
+// The mask of changed tiles comes back a tick late so that reading it doesn't
+// wait on the GPU. Nothing changing within twice the range on one tick means
+// nothing changes within the range on the next, so changes reach twice as far
+struct DirtyTiles {
+    tiles: Tiles,
+
+    mask_buf: GLuint,
+    frame_buffer: GLuint,
+    // Pixel pack buffer the mask is read into
+    pack_buf: GLuint,
+    // Whether the pack buffer holds a mask that wasn't looked at yet
+    pending: bool,
+    // Off to draw every tile, which the skipping is checked against
+    skip: bool,
+
+    shader: GLuint,
+    uni_front: GLint,
+    uni_back: GLint,
+}
 
-        gl::load_with(|s| my_glfw.get_proc_address_raw(s));
+impl DirtyTiles {
+    fn new(quad_vertex: &CStr, width: GLint, height: GLint) -> Self {
+        let tiles = Tiles::new(16, width as usize, height as usize, 2);
+
+        let dirty_frag = CString::new(include_str!("../glsl/dirty.frag")).unwrap();
+        let shader = program_from_sources(quad_vertex, &dirty_frag).unwrap();
+
+        let mask_buf = make_texture2d(
+            gl::TEXTURE5,
+            tiles.columns as GLint,
+            tiles.rows as GLint,
+            gl::CLAMP_TO_EDGE,
+            gl::NEAREST,
+        );
+
+        let mut frame_buffer = 0;
+        let mut pack_buf = 0;
         unsafe {
-            gl::Viewport(0, 0, width as i32, height as i32);
-            gl::ClearColor(0.3, 0.3, 0.5, 1.0);
+            gl::GenBuffers(1, &mut pack_buf);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, pack_buf);
+            gl::BufferData(
+                gl::PIXEL_PACK_BUFFER,
+                (tiles.columns * tiles.rows * 4) as GLsizeiptr,
+                null(),
+                gl::STREAM_READ,
+            );
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+
+            gl::GenFramebuffers(1, &mut frame_buffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, frame_buffer);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                mask_buf,
+                0,
+            );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+            gl::UseProgram(shader);
+            gl::Uniform1i(
+                get_uniform_location(shader, "tileSize"),
+                tiles.size as GLint,
+            );
+            gl::UseProgram(0);
         }
 
-        let gol_shader_source = format!(
-            "\
-#version 330 core
-out vec4 outColor;
+        DirtyTiles {
+            tiles,
 
-uniform sampler2D state;
-uniform vec2 scale;
+            mask_buf,
+            frame_buffer,
+            pack_buf,
+            pending: false,
+            skip: true,
 
-int get(vec2 offset) {{
-    return int(texture2D(state, (gl_FragCoord.xy + offset) / scale).r);
-}}
+            shader,
+            uni_front: get_uniform_location(shader, "front"),
+            uni_back: get_uniform_location(shader, "back"),
+        }
+    }
 
-void main() {{
-    int sum =
-        get(vec2(-1.0, -1.0)) +
-        get(vec2(-1.0,  0.0)) +
-        get(vec2(-1.0,  1.0)) +
-        get(vec2( 0.0, -1.0)) +
-        get(vec2( 0.0,  1.0)) +
-        get(vec2( 1.0, -1.0)) +
-        get(vec2( 1.0,  0.0)) +
-        get(vec2( 1.0,  1.0));
+    fn set_range(&mut self, range: usize) {
+        self.tiles.set_range(2 * range);
+    }
 
-    int current = get(vec2(0.0, 0.0));
+    // Draws the quad over every tile that can change on this tick, with
+    // scissor rectangles over runs of them. Expects the shader, frame buffer
+    // and quad's vertex array to be bound
+    fn draw_active(&mut self) {
+        let active = self.tiles.next_tick();
+        let (columns, size) = (self.tiles.columns, self.tiles.size as GLint);
 
-    float val = 0.0;
+        unsafe {
+            if !self.skip || active.iter().all(|&a| a) {
+                gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+                return;
+            }
 
-    if (current == 0) {{
-        if ({}) {{ val = 1.0; }}
-    }} else {{
-        if ({}) {{ val = 1.0; }}
-    }}
+            gl::Enable(gl::SCISSOR_TEST);
+            for (row, tiles) in active.chunks(columns).enumerate() {
+                let mut col = 0;
+                while col < columns {
+                    let start = col;
+                    while col < columns && tiles[col] {
+                        col += 1;
+                    }
 
-    gl_FragColor = vec4(val, val, val, 1.0);
-}}\
-        ",
-            born_cond, survive_cond
-        );
+                    if col > start {
+                        gl::Scissor(
+                            start as GLint * size,
+                            row as GLint * size,
+                            (col - start) as GLint * size,
+                            size,
+                        );
+                        gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+                    } else {
+                        col += 1;
+                    }
+                }
+            }
+            gl::Disable(gl::SCISSOR_TEST);
+        }
+    }
 
-        let copy_source = format!(
-            "\
-#version 330 core
-
-uniform sampler2D state;
-uniform vec2 scale;
-
-void main() {{
-    if (texture2D(state, gl_FragCoord.xy / scale).r == 1.0) {{
-        gl_FragColor = vec4({}, {}, {}, {}); // Live
-    }} else {{
-        gl_FragColor = vec4({}, {}, {}, {}); // Dead
-    }}
-}}\
-        ",
-            live_color.r(),
-            live_color.g(),
-            live_color.b(),
-            live_color.a(),
-            dead_color.r(),
-            dead_color.g(),
-            dead_color.b(),
-            dead_color.a(),
-        );
+    // Marks the tiles that changed on the last tick, and starts reading back
+    // which ones changed on this one. Expects the quad's vertex array to be
+    // bound
+    fn update(&mut self, front_tex: GLenum, back_tex: GLenum) {
+        if !self.skip {
+            return;
+        }
+        let len = self.tiles.columns * self.tiles.rows;
 
-        let quad_vertex = CString::new(include_str!("../glsl/quad.vert")).unwrap();
-        let gol_frag_shader = CString::new(gol_shader_source).unwrap();
-        let copy_frag_shader = CString::new(copy_source).unwrap();
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.pack_buf);
 
-        let gol_shader = program_from_sources(&quad_vertex, &gol_frag_shader).unwrap();
-        let gol_uni_state = get_uniform_location(gol_shader, "state");
-        let gol_uni_scale = get_uniform_location(gol_shader, "scale");
+            // The GPU had a whole tick to get this one done
+            if self.pending {
+                let mask = gl::MapBufferRange(
+                    gl::PIXEL_PACK_BUFFER,
+                    0,
+                    (len * 4) as GLsizeiptr,
+                    gl::MAP_READ_BIT,
+                ) as *const u32;
+
+                if mask.is_null() {
+                    self.tiles.mark_all();
+                } else {
+                    for (tile, texel) in std::slice::from_raw_parts(mask, len).iter().enumerate() {
+                        if texel & 0xFF != 0 {
+                            self.tiles.set_changed(tile);
+                        }
+                    }
+                    gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+                }
+            }
 
-        let copy_shader = program_from_sources(&quad_vertex, &copy_frag_shader).unwrap();
-        let copy_uni_state = get_uniform_location(copy_shader, "state");
-        let copy_uni_scale = get_uniform_location(copy_shader, "scale");
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.frame_buffer);
+            gl::UseProgram(self.shader);
+            gl::Uniform1i(self.uni_front, (front_tex - gl::TEXTURE0) as i32);
+            gl::Uniform1i(self.uni_back, (back_tex - gl::TEXTURE0) as i32);
+            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+
+            // Into the pack buffer, this returns before the pixels are there
+            gl::ReadPixels(
+                0,
+                0,
+                self.tiles.columns as GLsizei,
+                self.tiles.rows as GLsizei,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                null_mut(),
+            );
+
+            gl::UseProgram(0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+
+        self.pending = true;
+    }
+}
+
+impl Drop for DirtyTiles {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.frame_buffer);
+            gl::DeleteTextures(1, &self.mask_buf);
+            gl::DeleteBuffers(1, &self.pack_buf);
+            gl::DeleteProgram(self.shader);
+        }
+    }
+}
 
+impl Quad {
+    fn new() -> Self {
         #[rustfmt::skip]
             let vertices: [GLfloat; 8] = [
             -1.0, -1.0,
@@ -337,73 +978,406 @@ void main() {{
         let mut vertex_array: GLuint = 0;
         let mut vertex_buffer: GLuint = 0;
 
-        unsafe {
-            gl::GenVertexArrays(1, &mut vertex_array);
-            gl::BindVertexArray(vertex_array);
+        unsafe {
+            gl::GenVertexArrays(1, &mut vertex_array);
+            gl::BindVertexArray(vertex_array);
+
+            gl::GenBuffers(1, &mut vertex_buffer);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer);
+
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(&vertices) as isize,
+                vertices.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            );
+
+            gl::VertexAttribPointer(
+                0,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                (std::mem::size_of::<GLfloat>() * 2) as i32,
+                null(),
+            );
+            gl::EnableVertexAttribArray(0);
+        }
+
+        Quad {
+            vertex_array,
+            vertex_buffer,
+        }
+    }
+
+    fn bind(&self) {
+        unsafe {
+            gl::BindVertexArray(self.vertex_array);
+        }
+    }
+}
+
+impl Drop for Quad {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.vertex_buffer);
+            gl::DeleteVertexArrays(1, &self.vertex_array);
+        }
+    }
+}
+
+impl GpuGoL {
+    // Expects a current GL context with a viewport at least as big as the board
+    fn new(width: u32, height: u32, rule: &Rule) -> Self {
+        let quad_vertex = CString::new(include_str!("../glsl/quad.vert")).unwrap();
+        let gol_frag_shader = CString::new(include_str!("../glsl/gol.frag")).unwrap();
+
+        let gol_shader = program_from_sources(&quad_vertex, &gol_frag_shader).unwrap();
+        let gol_uni_state = get_uniform_location(gol_shader, "state");
+
+        let quad = Quad::new();
+
+        // Create texture to hold color buffer
+        let tex_width = width as i32;
+        let tex_height = height as i32;
+
+        let front_tex = gl::TEXTURE0;
+        let front_tex_id =
+            make_texture2d(front_tex, tex_width, tex_height, gl::REPEAT, gl::NEAREST);
+        let back_tex = gl::TEXTURE1;
+        let back_tex_id = make_texture2d(back_tex, tex_width, tex_height, gl::REPEAT, gl::NEAREST);
+
+        unsafe {
+            gl::UseProgram(gol_shader);
+            gl::Uniform1i(gol_uni_state, (back_tex - gl::TEXTURE0) as i32);
+            gl::UseProgram(0);
+        }
+
+        let mut gol_frame_buffer = 0;
+        unsafe {
+            // Create framebuffer
+            gl::GenFramebuffers(1, &mut gol_frame_buffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, gol_frame_buffer);
+
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                front_tex_id,
+                0,
+            );
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        let dirty = DirtyTiles::new(&quad_vertex, tex_width, tex_height);
+
+        let mut gpu = GpuGoL {
+            width,
+            height,
+
+            front_tex,
+            front_buf: front_tex_id,
+            back_tex,
+            back_buf: back_tex_id,
+
+            gol_frame_buffer,
+
+            gol_shader,
+            gol_uni_state,
+
+            quad,
+
+            ltl: None,
+            dirty,
+
+            rule: *rule,
+        };
+
+        gpu.set_rule(rule).unwrap();
+        gpu
+    }
+
+    // Draws the whole board on every tick from now on
+    fn draw_every_tile(&mut self) {
+        self.dirty.skip = false;
+    }
+
+    // Computes the next generation into the front texture
+    fn step(&mut self) {
+        unsafe {
+            // About to generate a new state, swap front and back
+            std::mem::swap(&mut self.back_buf, &mut self.front_buf);
+            std::mem::swap(&mut self.back_tex, &mut self.front_tex);
+
+            self.quad.bind();
+
+            // Larger than Life rules swap in their own shader
+            let (shader, uni_state) = match (&self.rule.kind, &self.ltl) {
+                (RuleKind::LargerThanLife(_), Some(ltl)) => {
+                    ltl.run(self.back_tex);
+                    (ltl.shader, ltl.uni_state)
+                }
+                _ => (self.gol_shader, self.gol_uni_state),
+            };
+
+            // Bind to the frame buffer since we need to render to it
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.gol_frame_buffer);
+
+            // Make sure to render to the newly swapped front
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                self.front_buf,
+                0,
+            );
+
+            gl::UseProgram(shader);
+
+            // Set updated uniform so they read from the right place
+            gl::Uniform1i(uni_state, (self.back_tex - gl::TEXTURE0) as i32);
+
+            // Use gol shader to compute next tick
+            self.dirty.draw_active();
+
+            // Unbind program
+            gl::UseProgram(0);
+
+            // Unbind so that we can render to the screen now
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        self.dirty.update(self.front_tex, self.back_tex);
+    }
+}
+
+impl Simulator for GpuGoL {
+    fn size(&self) -> (usize, usize) {
+        (self.width as usize, self.height as usize)
+    }
+
+    fn set_rule(&mut self, rule: &Rule) -> Result<(), &'static str> {
+        match &rule.kind {
+            RuleKind::Life { birth, survive, .. } => unsafe {
+                gl::UseProgram(self.gol_shader);
+                gl::Uniform1uiv(
+                    get_uniform_location(self.gol_shader, "birth"),
+                    8,
+                    birth.0.as_ptr(),
+                );
+                gl::Uniform1uiv(
+                    get_uniform_location(self.gol_shader, "survive"),
+                    8,
+                    survive.0.as_ptr(),
+                );
+                gl::Uniform1i(
+                    get_uniform_location(self.gol_shader, "states"),
+                    rule.states as GLint,
+                );
+                gl::Uniform1i(
+                    get_uniform_location(self.gol_shader, "topology"),
+                    rule.topology as GLint,
+                );
+            },
+            RuleKind::LargerThanLife(ltl) => {
+                let (width, height) = (self.width, self.height);
+                let passes = self.ltl.get_or_insert_with(|| {
+                    let quad_vertex = CString::new(include_str!("../glsl/quad.vert")).unwrap();
+                    LtlPasses::new(&quad_vertex, width as GLint, height as GLint)
+                });
+
+                unsafe {
+                    gl::UseProgram(passes.shader);
+                }
+                passes.set_rule(ltl, rule.states, rule.topology);
+            }
+        }
+
+        unsafe {
+            gl::UseProgram(0);
+        }
+
+        self.dirty.set_range(rule.range() as usize);
+        self.dirty.tiles.mark_all();
+        self.rule = *rule;
+        Ok(())
+    }
+
+    fn tick(&mut self, generations: u64) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+
+    fn set_cells(&mut self, region: Region, data: &[u8]) {
+        // Texture rows go up
+        let pixels = data
+            .chunks(region.width)
+            .rev()
+            .flatten()
+            .map(|&cell| DEAD | cell as u32)
+            .collect::<Vec<u32>>();
+
+        unsafe {
+            gl::ActiveTexture(self.front_tex); // GL_TEXTURE0-31
+        }
+        draw_on_texture(
+            region.x as u32,
+            self.height - (region.y + region.height) as u32,
+            &pixels,
+            region.width as u32,
+            region.height as u32,
+        );
+
+        self.dirty.tiles.mark_all();
+    }
+
+    fn read_state(&self) -> Vec<u8> {
+        let mut pixels = vec![0u32; (self.width * self.height) as usize];
+
+        unsafe {
+            gl::ActiveTexture(self.front_tex);
+            gl::GetTexImage(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+        }
+
+        pixels
+            .chunks(self.width as usize)
+            .rev()
+            .flatten()
+            .map(|&pixel| pixel as u8)
+            .collect()
+    }
+
+    fn finish(&self) {
+        unsafe {
+            gl::Finish();
+        }
+    }
+}
+
+impl Drop for GpuGoL {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.gol_frame_buffer);
+            gl::DeleteTextures(1, &self.front_buf);
+            gl::DeleteTextures(1, &self.back_buf);
+            gl::DeleteProgram(self.gol_shader);
+        }
+    }
+}
+
+impl WoL {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        scale: u32,
+        period: f64,
+        rules: Vec<Rule>,
+        colors: Colors,
+        engine: Engine,
+        seed: u64,
+        start: Start,
+        save: Saving,
+    ) -> Result<WoL, GlError> {
+        let mut my_glfw = glfw::init(glfw::LOG_ERRORS)
+            .map_err(|_| GlError::Context("Couldn't initialize GLFW"))?;
+
+        let (width, height) = my_glfw.with_primary_monitor(|_g, mon| {
+            let vid = mon.unwrap().get_video_mode().unwrap();
+            (vid.width, vid.height)
+        });
+
+        my_glfw.window_hint(WindowHint::ContextVersionMajor(3));
+        my_glfw.window_hint(WindowHint::ContextVersionMinor(3));
+        my_glfw.window_hint(WindowHint::OpenGlProfile(OpenGlProfileHint::Core));
+        my_glfw.window_hint(WindowHint::TransparentFramebuffer(true));
+
+        // Create a windowed mode window and its OpenGL context
+        let (mut window, events) = my_glfw
+            .create_window(
+                width,
+                height,
+                "Wallpaper of Life",
+                glfw::WindowMode::Windowed,
+            )
+            .ok_or(GlError::Context(
+                "Couldn't create a window with an OpenGL 3.3 core context",
+            ))?;
+
+        unsafe {
+            let xlib_xcb = x11_dl::xlib_xcb::Xlib_xcb::open().unwrap();
+
+            let disp = my_glfw.get_x11_display() as *mut x11_dl::xlib::Display;
+            let win = window.get_x11_window();
+
+            /* Get the XCB connection from the display */
+            let xcb_conn = (xlib_xcb.XGetXCBConnection)(disp);
+            if xcb_conn.is_null() {
+                panic!("Can't get xcb connection from display");
+            }
+
+            let xcb = x11rb::xcb_ffi::XCBConnection::from_raw_xcb_connection(xcb_conn as _, false)
+                .expect("Couldn't make XCBConnection from raw xcb connection");
+            make_window_wallpaper(&xcb, win as u32, width, height);
+        }
 
-            gl::GenBuffers(1, &mut vertex_buffer);
-            gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer);
+        // Make the window's context current
+        window.make_current();
 
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                std::mem::size_of_val(&vertices) as isize,
-                vertices.as_ptr() as *const c_void,
-                gl::STATIC_DRAW,
-            );
+        // window.set_all_polling(true);
+        window.set_refresh_polling(true);
+        window.set_key_polling(true);
+        window.set_mouse_button_polling(true);
+        window.set_cursor_pos_polling(true);
+        // window.set_cursor_enter_polling(true);
 
-            gl::VertexAttribPointer(
-                0,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                (std::mem::size_of::<GLfloat>() * 2) as i32,
-                0 as *const c_void,
-            );
-            gl::EnableVertexAttribArray(0);
+        gl::load_with(|s| my_glfw.get_proc_address_raw(s));
+        unsafe {
+            gl::Viewport(0, 0, width as i32, height as i32);
+            gl::ClearColor(0.3, 0.3, 0.5, 1.0);
         }
 
-        // Create texture to hold color buffer
-        let tex_width = (width / scale) as i32;
-        let tex_height = (height / scale) as i32;
+        let quad_vertex = CString::new(include_str!("../glsl/quad.vert")).unwrap();
+        let copy_frag_shader = CString::new(include_str!("../glsl/copy.frag")).unwrap();
 
-        let front_tex = gl::TEXTURE0;
-        let front_tex_id =
-            make_texture2d(front_tex, tex_width, tex_height, gl::REPEAT, gl::NEAREST);
-        let back_tex = gl::TEXTURE1;
-        let back_tex_id = make_texture2d(back_tex, tex_width, tex_height, gl::REPEAT, gl::NEAREST);
+        let copy_shader = program_from_sources(&quad_vertex, &copy_frag_shader).unwrap();
+        let copy_uni_state = get_uniform_location(copy_shader, "state");
+        let copy_uni_scale = get_uniform_location(copy_shader, "scale");
 
-        unsafe {
-            gl::UseProgram(gol_shader);
-            gl::Uniform1i(gol_uni_state, (back_tex - gl::TEXTURE0) as i32);
-            gl::Uniform2f(
-                gol_uni_scale,
-                (width / scale) as GLfloat,
-                (height / scale) as GLfloat,
-            );
+        let (board_width, board_height) = (width / scale, height / scale);
+        let gpu = GpuGoL::new(board_width, board_height, &rules[0]);
+        let cpu = match engine {
+            Engine::Gpu => None,
+            _ => Some(
+                engine
+                    .new_cpu(board_width as usize, board_height as usize, &rules[0])
+                    .map_err(GlError::Engine)?,
+            ),
+        };
+
+        // Filled in by set_rule
+        let palette_tex = gl::TEXTURE4;
+        let palette_buf = make_texture2d(palette_tex, 256, 1, gl::CLAMP_TO_EDGE, gl::NEAREST);
 
+        unsafe {
             gl::UseProgram(copy_shader);
-            gl::Uniform1i(copy_uni_state, (front_tex - gl::TEXTURE0) as i32);
             gl::Uniform2f(copy_uni_scale, (width) as GLfloat, (height) as GLfloat);
-        }
-
-        let mut gol_frame_buffer = 0;
-        unsafe {
-            // Create framebuffer
-            gl::GenFramebuffers(1, &mut gol_frame_buffer);
-            gl::BindFramebuffer(gl::FRAMEBUFFER, gol_frame_buffer);
-
-            gl::FramebufferTexture2D(
-                gl::FRAMEBUFFER,
-                gl::COLOR_ATTACHMENT0,
-                gl::TEXTURE_2D,
-                front_tex_id,
-                0,
+            gl::Uniform1i(
+                get_uniform_location(copy_shader, "palette"),
+                (palette_tex - gl::TEXTURE0) as i32,
             );
-
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::UseProgram(0);
         }
 
-        Self {
+        let mut wol = Self {
+            gpu,
+            cpu,
+
             glfw: my_glfw,
             width,
             height,
@@ -412,21 +1386,83 @@ void main() {{
             window,
             events,
 
-            front_tex,
-            front_buf: front_tex_id,
-            back_tex,
-            back_buf: back_tex_id,
-
-            gol_frame_buffer,
-
-            gol_shader,
-            gol_uni_state,
             copy_shader,
             copy_uni_state,
 
-            vertex_array,
-            vertex_buffer,
+            palette_buf,
+
+            rule: rules[0],
+            rules,
+            rule_index: 0,
+            colors,
+
+            rng: StdRng::seed_from_u64(seed),
+            brush: Brush::new(start.pattern.clone()),
+            save,
+        };
+
+        // Textures start out undefined, and replaying a session needs the
+        // same board
+        wol.engine().clear();
+        start.apply(wol.engine());
+
+        let rule = wol.rule;
+        wol.set_rule(&rule).map_err(GlError::Engine)?;
+        Ok(wol)
+    }
+
+    // Whichever engine runs the simulation
+    fn engine(&mut self) -> &mut dyn Simulator {
+        match &mut self.cpu {
+            Some(cpu) => cpu.as_mut(),
+            None => &mut self.gpu,
+        }
+    }
+
+    // Switches to another rule without touching the board. Cells in states
+    // the new rule does not have die out on the next tick
+    fn set_rule(&mut self, rule: &Rule) -> Result<(), &'static str> {
+        match &mut self.cpu {
+            Some(cpu) => cpu.set_rule(rule)?,
+            None => self.gpu.set_rule(rule)?,
+        }
+
+        let palette = self
+            .colors
+            .palette(rule.states)
+            .iter()
+            .flat_map(|c| [c.0, c.1, c.2, c.3])
+            .collect::<Vec<u8>>();
+
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE4);
+            gl::BindTexture(gl::TEXTURE_2D, self.palette_buf);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                rule.states as GLsizei,
+                1,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                palette.as_ptr() as *const c_void,
+            );
+
+            gl::UseProgram(self.copy_shader);
+            gl::Uniform1i(
+                get_uniform_location(self.copy_shader, "hex"),
+                rule.is_hexagonal() as GLint,
+            );
+            gl::Uniform1i(
+                get_uniform_location(self.copy_shader, "topology"),
+                rule.topology as GLint,
+            );
+            gl::UseProgram(0);
         }
+
+        self.rule = *rule;
+        Ok(())
     }
 
     fn main_loop(&mut self) {
@@ -451,12 +1487,58 @@ void main() {{
 
             // Poll for and process events
             self.glfw.wait_events_timeout(time_to_next_tick);
-            for (_, event) in glfw::flush_messages(&self.events) {
+            let events = glfw::flush_messages(&self.events).collect::<Vec<_>>();
+            for (_, event) in events {
                 match event {
                     glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
                         self.window.set_should_close(true);
                         should_redraw = false;
                     }
+                    glfw::WindowEvent::Key(Key::Tab, _, Action::Press, _) => {
+                        // On to the next rule the engine can run
+                        for offset in 1..=self.rules.len() {
+                            let index = (self.rule_index + offset) % self.rules.len();
+                            let rule = self.rules[index];
+                            match self.set_rule(&rule) {
+                                Ok(()) => {
+                                    self.rule_index = index;
+                                    println!("Rule: {}", rule);
+                                    break;
+                                }
+                                Err(e) => println!("{}: {}", rule, e),
+                            }
+                        }
+                        should_redraw = true;
+                    }
+                    glfw::WindowEvent::Key(Key::F, _, Action::Press, _) => {
+                        let rule = self.rule;
+                        println!("{}", fast_forward(self.engine(), &rule, 1000));
+                        should_redraw = true;
+                    }
+                    glfw::WindowEvent::Key(Key::LeftBracket, _, Action::Press, _) => {
+                        println!("Stamping {}", self.brush.cycle(-1));
+                    }
+                    glfw::WindowEvent::Key(Key::RightBracket, _, Action::Press, _) => {
+                        println!("Stamping {}", self.brush.cycle(1));
+                    }
+                    glfw::WindowEvent::Key(Key::O, _, Action::Press, mods) => {
+                        let step = if mods.contains(Modifiers::Shift) {
+                            -1
+                        } else {
+                            1
+                        };
+                        println!("Stamping turned {}", self.brush.turn(step));
+                    }
+                    glfw::WindowEvent::Key(Key::S, _, Action::Press, _) => {
+                        let engine: &dyn Simulator = match &self.cpu {
+                            Some(cpu) => cpu.as_ref(),
+                            None => &self.gpu,
+                        };
+                        match save_board(engine, &self.rule, &self.save) {
+                            Ok(path) => println!("Saved the board to {}", path.display()),
+                            Err(e) => println!("{}", e),
+                        }
+                    }
                     glfw::WindowEvent::Refresh => {
                         should_redraw = true;
                     }
@@ -465,122 +1547,16 @@ void main() {{
                             continue;
                         }
 
-                        let x = mouse_pos.0 / self.scale;
-                        let y = (self.height - mouse_pos.1) / self.scale;
-
-                        let ctrl = mods.contains(Modifiers::Control);
-                        let shift = mods.contains(Modifiers::Shift);
-
-                        unsafe {
-                            gl::ActiveTexture(self.front_tex); // GL_TEXTURE0-31
-                        }
-
-                        match (ctrl, shift, but) {
-                            // Left Click
-                            (false, _, MouseButton::Button1) => {
-                                draw_on_texture(x, y, &[0x00000000], 1, 1);
-                            }
-
-                            // Control + Left Click
-                            (true, false, MouseButton::Button1) => {
-                                let total_pixels =
-                                    self.width / self.scale * self.height / self.scale;
-                                let pixels = (0..total_pixels)
-                                    .map(|_| {
-                                        if rand::random::<f32>() > 0.5 {
-                                            0xFFFFFFFF
-                                        } else {
-                                            0x00000000
-                                        }
-                                    })
-                                    .collect::<Vec<u32>>();
-
-                                draw_on_texture(
-                                    0,
-                                    0,
-                                    &pixels,
-                                    self.width / self.scale,
-                                    self.height / self.scale,
-                                );
-                            }
-
-                            // Control + Shift + Left Click
-                            (true, true, MouseButton::Button1) => {
-                                let total_pixels =
-                                    self.width / self.scale * self.height / self.scale;
-                                let pixels = vec![0; total_pixels as usize];
-                                draw_on_texture(
-                                    0,
-                                    0,
-                                    &pixels,
-                                    self.width / self.scale,
-                                    self.height / self.scale,
-                                );
-                            }
-
-                            // Right Click
-                            (_, _, MouseButton::Button2) => {
-                                draw_on_texture(x, y, &[0xffffffff], 1, 1);
-                            }
-
-                            // Middle Click
-                            (false, false, MouseButton::Button3) => {
-                                draw_on_texture(
-                                    x,
-                                    y,
-                                    &[
-                                        0x00000000, 0xffffffff, 0x00000000, 0xffffffff, 0x00000000,
-                                        0x00000000, 0xffffffff, 0xffffffff, 0xffffffff,
-                                    ],
-                                    3,
-                                    3,
-                                );
-                            }
-
-                            // Control + Middle Click
-                            (true, false, MouseButton::Button3) => {
-                                draw_on_texture(
-                                    x,
-                                    y,
-                                    &[
-                                        0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0x00000000,
-                                        0x00000000, 0x00000000, 0xffffffff, 0x00000000,
-                                    ],
-                                    3,
-                                    3,
-                                );
-                            }
-
-                            // Shift + Middle Click
-                            (false, true, MouseButton::Button3) => {
-                                draw_on_texture(
-                                    x,
-                                    y,
-                                    &[
-                                        0x00000000, 0xffffffff, 0x00000000, 0x00000000, 0x00000000,
-                                        0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff,
-                                    ],
-                                    3,
-                                    3,
-                                );
-                            }
-
-                            // Control + Shift + Middle Click
-                            (true, true, MouseButton::Button3) => {
-                                draw_on_texture(
-                                    x,
-                                    y,
-                                    &[
-                                        0xffffffff, 0xffffffff, 0xffffffff, 0x00000000, 0x00000000,
-                                        0xffffffff, 0x00000000, 0xffffffff, 0x00000000,
-                                    ],
-                                    3,
-                                    3,
-                                );
-                            }
-                            _ => {}
-                        }
-
+                        let Some(cell) = self.cell_at(mouse_pos) else {
+                            continue;
+                        };
+
+                        // Not through engine() to borrow the RNG alongside
+                        let engine: &mut dyn Simulator = match &mut self.cpu {
+                            Some(cpu) => cpu.as_mut(),
+                            None => &mut self.gpu,
+                        };
+                        click(engine, cell, but, mods, &mut self.rng, &self.brush.pattern);
                         should_redraw = true;
                     }
                     glfw::WindowEvent::CursorPos(x, y) => {
@@ -603,40 +1579,37 @@ void main() {{
         }
     }
 
-    fn draw(&mut self, new_tick: bool) {
-        if new_tick {
-            unsafe {
-                // About to generate a new state, swap front and back
-                std::mem::swap(&mut self.back_buf, &mut self.front_buf);
-                std::mem::swap(&mut self.back_tex, &mut self.front_tex);
-
-                // Bind to the frame buffer since we need to render to it
-                gl::BindFramebuffer(gl::FRAMEBUFFER, self.gol_frame_buffer);
-
-                // Make sure to render to the newly swapped front
-                gl::FramebufferTexture2D(
-                    gl::FRAMEBUFFER,
-                    gl::COLOR_ATTACHMENT0,
-                    gl::TEXTURE_2D,
-                    self.front_buf,
-                    0,
-                );
+    // Cell under a point on the window, matching how the copy shader draws it,
+    // with rows going down. Hexagons past the edge of a plane have no cell
+    fn cell_at(&self, (x, y): (u32, u32)) -> Option<(usize, usize)> {
+        let x = x as f64 / self.scale as f64;
+        let y = (self.height - y) as f64 / self.scale as f64;
 
-                gl::UseProgram(self.gol_shader);
+        let (x, y) = if self.rule.is_hexagonal() {
+            hex_cell(x, y)
+        } else {
+            (x.floor() as i64, y.floor() as i64)
+        };
 
-                // Set updated uniform so they read from the right place
-                gl::Uniform1i(self.gol_uni_state, (self.back_tex - gl::TEXTURE0) as i32);
+        let height = (self.height / self.scale) as i64;
+        let (x, y) = self
+            .rule
+            .topology
+            .wrap(x, y, (self.width / self.scale) as i64, height)?;
 
-                // Use gol shader to compute next tick
-                gl::BindVertexArray(self.vertex_array);
-                gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+        Some((x as usize, (height - 1 - y) as usize))
+    }
 
-                // Unbind program
-                gl::UseProgram(0);
+    fn draw(&mut self, new_tick: bool) {
+        if new_tick {
+            self.engine().tick(1);
+        }
 
-                // Unbind so that we can render to the screen now
-                gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
-            }
+        // The GPU draws whatever state the CPU engine is in
+        if let Some(cpu) = &self.cpu {
+            let (width, height) = cpu.size();
+            self.gpu
+                .set_cells(Region::new(0, 0, width, height), &cpu.read_state());
         }
 
         unsafe {
@@ -644,8 +1617,11 @@ void main() {{
 
             gl::UseProgram(self.copy_shader);
 
-            gl::Uniform1i(self.copy_uni_state, (self.front_tex - gl::TEXTURE0) as i32);
-            gl::BindVertexArray(self.vertex_array);
+            gl::Uniform1i(
+                self.copy_uni_state,
+                (self.gpu.front_tex - gl::TEXTURE0) as i32,
+            );
+            self.gpu.quad.bind();
             gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
 
             gl::UseProgram(0);
@@ -655,6 +1631,328 @@ void main() {{
     }
 }
 
+impl Drop for WoL {
+    fn drop(&mut self) {
+        // The window, and with it the context, is dropped after this runs
+        unsafe {
+            gl::DeleteProgram(self.copy_shader);
+            gl::DeleteTextures(1, &self.palette_buf);
+        }
+    }
+}
+
+// A hidden window with a GL 3.3 context for running the shaders without
+// drawing anything. Falls back to OSMesa, which runs on llvmpipe, when the
+// display has no GL
+fn offscreen_context(width: u32, height: u32) -> Result<(glfw::Glfw, Window), &'static str> {
+    let mut my_glfw = glfw::init(glfw::LOG_ERRORS).map_err(|_| "Couldn't initialize GLFW")?;
+
+    my_glfw.window_hint(WindowHint::ContextVersionMajor(3));
+    my_glfw.window_hint(WindowHint::ContextVersionMinor(3));
+    my_glfw.window_hint(WindowHint::OpenGlProfile(OpenGlProfileHint::Core));
+    my_glfw.window_hint(WindowHint::Visible(false));
+
+    let create = |my_glfw: &glfw::Glfw| {
+        my_glfw.create_window(
+            width,
+            height,
+            "Wallpaper of Life",
+            glfw::WindowMode::Windowed,
+        )
+    };
+    let created = match create(&my_glfw) {
+        Some(created) => Some(created),
+        None => {
+            my_glfw.window_hint(WindowHint::ContextCreationApi(ContextCreationApi::OsMesa));
+            create(&my_glfw)
+        }
+    };
+    let (mut window, _) =
+        created.ok_or("Couldn't create an OpenGL 3.3 core context, even with OSMesa")?;
+
+    window.make_current();
+    gl::load_with(|s| my_glfw.get_proc_address_raw(s));
+    unsafe {
+        gl::Viewport(0, 0, width as i32, height as i32);
+    }
+
+    Ok((my_glfw, window))
+}
+
+// What a mouse button does to the cell under the cursor, on any backend
+fn click(
+    engine: &mut dyn Simulator,
+    cell: (usize, usize),
+    button: MouseButton,
+    mods: Modifiers,
+    rng: &mut StdRng,
+    pattern: &Pattern,
+) {
+    let ctrl = mods.contains(Modifiers::Control);
+    let shift = mods.contains(Modifiers::Shift);
+
+    match (ctrl, shift, button) {
+        // Left Click
+        (false, _, MouseButton::Button1) => {
+            stamp(engine, cell, 1, &[0]);
+        }
+
+        // Control + Left Click
+        (true, false, MouseButton::Button1) => {
+            engine.randomize(0.5, rng.gen());
+        }
+
+        // Control + Shift + Left Click
+        (true, true, MouseButton::Button1) => {
+            engine.clear();
+        }
+
+        // Right Click
+        (_, _, MouseButton::Button2) => {
+            stamp(engine, cell, 1, &[1]);
+        }
+
+        // Middle Click, mirrored top to bottom with Control and left to right
+        // with Shift
+        (_, _, MouseButton::Button3) => {
+            let pattern = pattern.flipped(shift, ctrl);
+            stamp(engine, cell, pattern.width, &pattern.cells);
+        }
+        _ => {}
+    }
+}
+
+// What the board starts with
+#[derive(Debug, Clone, Default)]
+struct Start {
+    // Put in the middle of the board
+    pattern: Option<Pattern>,
+    // Put with their top left corner on a cell
+    stamps: Vec<(Pattern, (usize, usize))>,
+}
+
+impl Start {
+    fn is_empty(&self) -> bool {
+        self.pattern.is_none() && self.stamps.is_empty()
+    }
+
+    fn apply(&self, engine: &mut dyn Simulator) {
+        if let Some(pattern) = &self.pattern {
+            stamp_centered(engine, pattern);
+        }
+
+        let (width, height) = engine.size();
+        for (pattern, (x, y)) in &self.stamps {
+            if x + pattern.width > width || y + pattern.height > height {
+                println!(
+                    "The {}x{} pattern at {},{} doesn't fit on the {}x{} board",
+                    pattern.width, pattern.height, x, y, width, height
+                );
+                continue;
+            }
+            engine.set_cells(
+                Region::new(*x, *y, pattern.width, pattern.height),
+                &pattern.cells,
+            );
+        }
+    }
+}
+
+// The pattern middle click puts down, which [ and ] switch through the
+// library and o and O turn
+struct Brush {
+    // As it was loaded, and as it gets stamped
+    original: Pattern,
+    pattern: Pattern,
+    // Library entry the pattern is, if it is one
+    entry: Option<usize>,
+    symmetry: Symmetry,
+}
+
+impl Brush {
+    // The glider unless there is a pattern
+    fn new(pattern: Option<Pattern>) -> Brush {
+        let (pattern, entry) = match pattern {
+            Some(pattern) => (pattern, None),
+            None => {
+                let entry = library::LIBRARY
+                    .iter()
+                    .position(|entry| entry.name == "glider")
+                    .unwrap();
+                (library::LIBRARY[entry].pattern(), Some(entry))
+            }
+        };
+
+        Brush {
+            original: pattern.clone(),
+            pattern,
+            entry,
+            symmetry: Symmetry::Identity,
+        }
+    }
+
+    // Steps through the orientations, see Symmetry::ALL
+    fn turn(&mut self, step: isize) -> Symmetry {
+        self.symmetry = self.symmetry.cycled(step);
+        self.pattern = self.original.transformed(self.symmetry);
+        self.symmetry
+    }
+
+    // Moves to a library entry before or after the current one
+    fn cycle(&mut self, step: isize) -> &'static library::Entry {
+        let count = library::LIBRARY.len() as isize;
+        let entry = match self.entry {
+            Some(entry) => (entry as isize + step).rem_euclid(count) as usize,
+            None if step < 0 => count as usize - 1,
+            None => 0,
+        };
+
+        self.entry = Some(entry);
+        self.original = library::LIBRARY[entry].pattern();
+        self.pattern = self.original.transformed(self.symmetry);
+        &library::LIBRARY[entry]
+    }
+}
+
+// A pattern file, or a library pattern when there is no such file
+fn find_pattern(name: &str) -> Result<Pattern, String> {
+    let path = Path::new(name);
+    if path.exists() {
+        return Pattern::load(path);
+    }
+
+    library::find(name)
+        .map(|entry| entry.pattern())
+        .ok_or_else(|| {
+            format!(
+                "There is no file or library pattern named '{}', see the patterns command",
+                name
+            )
+        })
+}
+
+// Puts a pattern given in rows going down with its bottom left corner on
+// a cell. Patterns that don't fit on the board are left out
+fn stamp(engine: &mut dyn Simulator, (x, y): (usize, usize), width: usize, data: &[u8]) {
+    let height = data.len() / width;
+    let (board_width, _) = engine.size();
+
+    if let Some(top) = (y + 1).checked_sub(height) {
+        if x + width <= board_width {
+            engine.set_cells(Region::new(x, top, width, height), data);
+        }
+    }
+}
+
+// Puts a pattern in the middle of the board, either the whole pattern or
+// its origin
+fn stamp_centered(engine: &mut dyn Simulator, pattern: &Pattern) {
+    let (width, height) = engine.size();
+    let corner = match pattern.origin {
+        Some((x, y)) => (
+            usize::try_from((width / 2) as i64 - x).ok(),
+            usize::try_from((height / 2) as i64 - y).ok(),
+        ),
+        None => (
+            width.checked_sub(pattern.width).map(|x| x / 2),
+            height.checked_sub(pattern.height).map(|y| y / 2),
+        ),
+    };
+
+    let (x, y) = match corner {
+        (Some(x), Some(y)) if x + pattern.width <= width && y + pattern.height <= height => (x, y),
+        _ => {
+            println!(
+                "The {}x{} pattern doesn't fit on the {}x{} board",
+                pattern.width, pattern.height, width, height
+            );
+            return;
+        }
+    };
+
+    engine.set_cells(
+        Region::new(x, y, pattern.width, pattern.height),
+        &pattern.cells,
+    );
+}
+
+// Writes the cells that aren't dead to a new file in the directory, named
+// after the time
+fn save_board(engine: &dyn Simulator, rule: &Rule, save: &Saving) -> Result<PathBuf, String> {
+    let (width, height) = engine.size();
+    let pattern = Pattern::from_board(width, height, &engine.read_state(), *rule)
+        .ok_or("There are no cells to save")?;
+
+    let directory = &save.dir;
+    std::fs::create_dir_all(directory)
+        .map_err(|e| format!("Couldn't create {}: {}", directory.display(), e))?;
+    let (extension, text) = match save.format {
+        SaveFormat::Rle => ("rle", pattern.to_rle()),
+        SaveFormat::Macrocell => ("mc", pattern.to_macrocell()),
+    };
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_secs());
+    // Saving twice in a second doesn't overwrite the first one
+    let path = (0..)
+        .map(|n| match n {
+            0 => directory.join(format!("wol-{}.{}", time, extension)),
+            n => directory.join(format!("wol-{}-{}.{}", time, n, extension)),
+        })
+        .find(|path| !path.exists())
+        .unwrap();
+
+    std::fs::write(&path, text).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+// Jumps ahead with HashLife when it can run the rule and topology, and
+// ticks through the generations one by one otherwise. Returns how it went
+fn fast_forward(engine: &mut dyn Simulator, rule: &Rule, generations: u64) -> String {
+    let (width, height) = engine.size();
+    let board = Region::new(0, 0, width, height);
+    let start = Instant::now();
+
+    let how = match HashLifeBoard::new(width, height, rule) {
+        Ok(mut life) => {
+            life.set_cells(board, &engine.read_state());
+            life.tick(generations);
+            engine.set_cells(board, &life.read_state());
+            String::new()
+        }
+        Err(e) => {
+            engine.tick(generations);
+            format!(" one at a time ({})", e)
+        }
+    };
+
+    format!(
+        "Fast forwarded {} generations{} in {:.1} ms, population {}",
+        generations,
+        how,
+        start.elapsed().as_secs_f64() * 1000.0,
+        engine.population()
+    )
+}
+
+// Same rounding as hexCell in the copy shader
+fn hex_cell(x: f64, y: f64) -> (i64, i64) {
+    let r = y - 0.5;
+    let q = x - 0.5 - r * 0.5;
+    let cube = [q, r, -q - r];
+    let mut rounded = cube.map(f64::round);
+    let diff = [0, 1, 2].map(|i| (rounded[i] - cube[i]).abs());
+
+    if diff[0] > diff[1] && diff[0] > diff[2] {
+        rounded[0] = -rounded[1] - rounded[2];
+    } else if diff[1] > diff[2] {
+        rounded[1] = -rounded[0] - rounded[2];
+    }
+
+    (rounded[0] as i64, rounded[1] as i64)
+}
+
 fn draw_on_texture(x: u32, y: u32, pixels: &[u32], w: u32, h: u32) {
     unsafe {
         gl::TexSubImage2D(
@@ -671,6 +1969,33 @@ fn draw_on_texture(x: u32, y: u32, pixels: &[u32], w: u32, h: u32) {
     }
 }
 
+// Single channel integer texture, used for neighbor counts
+fn make_count_texture2d(texture: GLenum, width: GLint, height: GLint) -> GLuint {
+    let mut texture_id = 0;
+
+    unsafe {
+        gl::GenTextures(1, &mut texture_id);
+        gl::ActiveTexture(texture); // GL_TEXTURE0-31
+        gl::BindTexture(gl::TEXTURE_2D, texture_id);
+
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::R32I as i32,
+            width,
+            height,
+            0,
+            gl::RED_INTEGER,
+            gl::INT,
+            null(),
+        );
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+    }
+
+    texture_id
+}
+
 fn get_uniform_location(program: GLuint, uniform: &str) -> GLint {
     let uniform_cstr = CString::new(uniform).unwrap();
     unsafe { gl::GetUniformLocation(program, uniform_cstr.as_ptr() as *const GLchar) }
@@ -805,15 +2130,12 @@ fn make_whitespace_cstring(len: usize) -> CString {
     // allocate buffer of correct size
     let mut buffer: Vec<u8> = Vec::with_capacity(len + 1);
     // fill it with len spaces
-    buffer.resize(len as usize, b' ');
+    buffer.resize(len, b' ');
     // convert buffer to CString
     unsafe { CString::from_vec_unchecked(buffer) }
 }
 
-unsafe fn make_window_wallpaper(raw_xcb_conn: *mut c_void, window: u32, width: u32, height: u32) {
-    let xcb = x11rb::xcb_ffi::XCBConnection::from_raw_xcb_connection(raw_xcb_conn as _, false)
-        .expect("Couldn't make XCBConnection from raw xcb connection");
-
+fn make_window_wallpaper(xcb: &impl Connection, window: u32, width: u32, height: u32) {
     xcb.sync().unwrap();
 
     xcb.unmap_window(window).unwrap();
@@ -0,0 +1,876 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::rule::Rule;
+
+// Patterns with more cells than this are refused instead of filling the memory
+pub const MAX_CELLS: usize = 1 << 28;
+
+// Cells of a pattern in rows going down, 0 being dead and 1 alive as on the
+// boards
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<u8>,
+    // Rule the file says the pattern runs in
+    pub rule: Option<Rule>,
+    // Cell that goes in the middle of the board, from the top left corner,
+    // for formats with coordinates around an origin. It can be outside of the
+    // cells. Others center the whole pattern
+    pub origin: Option<(i64, i64)>,
+}
+
+// The eight ways to turn and mirror a square, named as in Golly. Rows go
+// down, so rcw turns patterns clockwise on screen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Symmetry {
+    #[default]
+    Identity,
+    Rcw,
+    Flip,
+    Rccw,
+    FlipX,
+    SwapXyFlip,
+    FlipY,
+    SwapXy,
+}
+
+impl Symmetry {
+    // Turns going clockwise, first as they are and then mirrored
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity,
+        Symmetry::Rcw,
+        Symmetry::Flip,
+        Symmetry::Rccw,
+        Symmetry::FlipX,
+        Symmetry::SwapXyFlip,
+        Symmetry::FlipY,
+        Symmetry::SwapXy,
+    ];
+
+    const NAMES: [&'static str; 8] = [
+        "ident",
+        "rcw",
+        "flip",
+        "rccw",
+        "flip_x",
+        "swap_xy_flip",
+        "flip_y",
+        "swap_xy",
+    ];
+
+    // Where a cell goes, as x and y made of the old x and y
+    fn matrix(self) -> [[i64; 2]; 2] {
+        match self {
+            Symmetry::Identity => [[1, 0], [0, 1]],
+            Symmetry::Rcw => [[0, -1], [1, 0]],
+            Symmetry::Flip => [[-1, 0], [0, -1]],
+            Symmetry::Rccw => [[0, 1], [-1, 0]],
+            Symmetry::FlipX => [[-1, 0], [0, 1]],
+            Symmetry::SwapXyFlip => [[0, -1], [-1, 0]],
+            Symmetry::FlipY => [[1, 0], [0, -1]],
+            Symmetry::SwapXy => [[0, 1], [1, 0]],
+        }
+    }
+
+    // The one steps away in ALL, wrapping around
+    pub fn cycled(self, step: isize) -> Symmetry {
+        let index = Symmetry::ALL.iter().position(|&s| s == self).unwrap();
+        Symmetry::ALL[(index as isize + step).rem_euclid(8) as usize]
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Symmetry::NAMES
+            .iter()
+            .position(|&name| name == value.to_lowercase())
+            .map(|i| Symmetry::ALL[i])
+            .ok_or_else(|| {
+                format!(
+                    "unknown transform '{}', expected one of {}",
+                    value,
+                    Symmetry::NAMES.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let index = Symmetry::ALL.iter().position(|s| s == self).unwrap();
+        write!(f, "{}", Symmetry::NAMES[index])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Rle,
+    // Plaintext .cells
+    Cells,
+    Life105,
+    Life106,
+    Macrocell,
+}
+
+impl Pattern {
+    // Reads a pattern in any of the formats, telling which from the
+    // extension and the contents
+    pub fn load(path: &Path) -> Result<Pattern, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+        let extension = path.extension().and_then(|e| e.to_str());
+        Pattern::parse(&text, extension).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str, extension: Option<&str>) -> Result<Pattern, String> {
+        match detect(text, extension) {
+            Format::Rle => Pattern::from_rle(text),
+            Format::Cells => Pattern::from_plaintext(text),
+            Format::Life105 => Pattern::from_life_105(text),
+            Format::Life106 => Pattern::from_life_106(text),
+            Format::Macrocell => Pattern::from_macrocell(text),
+        }
+    }
+
+    // Reads run length encoded cells, as written by Golly:
+    //
+    //   #N Glider
+    //   x = 3, y = 3, rule = B3/S23
+    //   bo$2bo$3o!
+    //
+    // Two state patterns use b for dead and o for alive cells, multistate
+    // ones . for dead and A to X, pA to yO for the other states. $ ends a
+    // row and ! the pattern
+    pub fn from_rle(text: &str) -> Result<Pattern, String> {
+        let mut size = (0, 0);
+        let mut rule = None;
+        // Only the rows with cells in them, by their y
+        let mut rows: Vec<(usize, Vec<u8>)> = Vec::new();
+        let (mut y, mut width) = (0usize, 0usize);
+        let mut count: Option<usize> = None;
+        let mut prefix = None;
+        let mut header_done = false;
+
+        'lines: for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                // Old files give the rule in a comment
+                if let Some(r) = comment.strip_prefix('r') {
+                    rule = Some(parse_rule(r.trim()).map_err(error)?);
+                }
+                continue;
+            }
+            if !header_done && line.starts_with('x') {
+                header_done = true;
+                size = parse_header(line, &mut rule).map_err(error)?;
+                continue;
+            }
+            header_done = true;
+
+            for c in line.chars() {
+                match c {
+                    '0'..='9' => {
+                        let digit = c as usize - '0' as usize;
+                        count = count
+                            .unwrap_or(0)
+                            .checked_mul(10)
+                            .and_then(|count| count.checked_add(digit))
+                            .filter(|&count| count <= MAX_CELLS);
+                        if count.is_none() {
+                            return Err(error("run is too long".to_string()));
+                        }
+                        continue;
+                    }
+                    c if c.is_whitespace() => continue,
+                    // A state letter has to follow its prefix
+                    '!' | '$' if prefix.is_some() => {
+                        let p = prefix.unwrap();
+                        return Err(error(format!("unexpected '{}' after '{}'", c, p)));
+                    }
+                    '!' => break 'lines,
+                    '$' => {
+                        y += count.unwrap_or(1);
+                        if width.saturating_mul(y + 1) > MAX_CELLS {
+                            return Err(error("pattern is too big".to_string()));
+                        }
+                    }
+                    'p'..='y' if prefix.is_none() => {
+                        prefix = Some(c);
+                        continue;
+                    }
+                    _ => {
+                        let state = match (prefix.take(), c) {
+                            (None, 'b' | '.') => 0,
+                            (None, 'o') => 1,
+                            (prefix, 'A'..='X') => {
+                                let high = prefix.map_or(0, |p| p as usize - 'p' as usize + 1);
+                                let state = high * 24 + (c as usize - 'A' as usize + 1);
+                                if state > 255 {
+                                    return Err(error(format!("state {} is too high", state)));
+                                }
+                                state as u8
+                            }
+                            // Golly reads any other letter as alive
+                            (None, c) if c.is_ascii_lowercase() => 1,
+                            (Some(p), c) => {
+                                return Err(error(format!("unexpected '{}' after '{}'", c, p)))
+                            }
+                            (None, c) => return Err(error(format!("unexpected '{}'", c))),
+                        };
+
+                        if rows.last().is_none_or(|&(last, _)| last != y) {
+                            rows.push((y, Vec::new()));
+                        }
+                        let row = &mut rows.last_mut().unwrap().1;
+                        let run = count.unwrap_or(1);
+                        width = width.max(row.len() + run);
+                        if width.saturating_mul(y + 1) > MAX_CELLS {
+                            return Err(error("pattern is too big".to_string()));
+                        }
+                        row.extend(std::iter::repeat_n(state, run));
+                    }
+                }
+                count = None;
+            }
+        }
+
+        if let Some(p) = prefix {
+            return Err(format!("pattern ends after '{}'", p));
+        }
+
+        // Trust the cells over the header when they don't agree
+        let width = width.max(size.0);
+        let height = (y + 1).max(size.1);
+        if width == 0 || height == 0 {
+            return Err("pattern is empty".to_string());
+        }
+        if width.saturating_mul(height) > MAX_CELLS {
+            return Err(format!("{}x{} pattern is too big", width, height));
+        }
+
+        let mut cells = vec![0; width * height];
+        for (y, row) in &rows {
+            cells[y * width..y * width + row.len()].copy_from_slice(row);
+        }
+
+        Ok(Pattern {
+            width,
+            height,
+            cells,
+            rule,
+            origin: None,
+        })
+    }
+
+    // Reads rows of . for dead and O for alive cells, with comment lines
+    // starting with !
+    pub fn from_plaintext(text: &str) -> Result<Pattern, String> {
+        let mut rows = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            if line.starts_with('!') {
+                continue;
+            }
+
+            let row = line
+                .trim_end()
+                .chars()
+                .map(|c| match c {
+                    '.' => Ok(0),
+                    'O' | '*' => Ok(1),
+                    c => Err(format!("line {}: unexpected '{}'", number + 1, c)),
+                })
+                .collect::<Result<Vec<u8>, String>>()?;
+            rows.push(row);
+        }
+
+        while rows.last().is_some_and(|row| row.is_empty()) {
+            rows.pop();
+        }
+
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if width == 0 {
+            return Err("pattern is empty".to_string());
+        }
+
+        let mut cells = vec![0; width * rows.len()];
+        for (y, row) in rows.iter().enumerate() {
+            cells[y * width..y * width + row.len()].copy_from_slice(row);
+        }
+
+        Ok(Pattern {
+            width,
+            height: rows.len(),
+            cells,
+            rule: None,
+            origin: None,
+        })
+    }
+
+    // Reads blocks of . and * rows, each placed by a #P line with the
+    // position of its top left corner around the origin:
+    //
+    //   #Life 1.05
+    //   #R 23/3
+    //   #P -1 -1
+    //   .*.
+    //   ..*
+    //   ***
+    pub fn from_life_105(text: &str) -> Result<Pattern, String> {
+        let mut alive = Vec::new();
+        let mut rule = None;
+        let mut block = (0, 0);
+        let mut row = 0;
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+
+            if let Some(tag) = line.strip_prefix('#') {
+                let (kind, rest) = tag.split_at(tag.chars().next().map_or(0, char::len_utf8));
+                match kind {
+                    "P" => {
+                        let position = parse_numbers(rest).map_err(error)?;
+                        block = position;
+                        row = 0;
+                    }
+                    "R" => rule = Some(parse_rule(rest.trim()).map_err(error)?),
+                    "N" => rule = Some(parse_rule("23/3").unwrap()),
+                    // #Life, #D descriptions
+                    _ => {}
+                }
+                continue;
+            }
+
+            for (column, c) in line.chars().enumerate() {
+                match c {
+                    '.' => {}
+                    '*' | 'O' => alive.push((block.0 + column as i64, block.1 + row)),
+                    c => return Err(error(format!("unexpected '{}'", c))),
+                }
+            }
+            row += 1;
+        }
+
+        Pattern::from_coordinates(&alive, rule)
+    }
+
+    // Reads the coordinates of alive cells around the origin, one cell per
+    // line, with y going down:
+    //
+    //   #Life 1.06
+    //   0 -1
+    //   1 0
+    //   -1 1
+    //   0 1
+    //   1 1
+    pub fn from_life_106(text: &str) -> Result<Pattern, String> {
+        let mut alive = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let position =
+                parse_numbers(line).map_err(|e| format!("line {}: {}", number + 1, e))?;
+            alive.push(position);
+        }
+
+        Pattern::from_coordinates(&alive, None)
+    }
+
+    fn from_coordinates(alive: &[(i64, i64)], rule: Option<Rule>) -> Result<Pattern, String> {
+        let Some(&(first_x, first_y)) = alive.first() else {
+            return Err("pattern is empty".to_string());
+        };
+
+        let (mut min, mut max) = ((first_x, first_y), (first_x, first_y));
+        for &(x, y) in alive {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+
+        let (width, height) = ((max.0 - min.0 + 1) as usize, (max.1 - min.1 + 1) as usize);
+        if width.saturating_mul(height) > MAX_CELLS {
+            return Err(format!("{}x{} pattern is too big", width, height));
+        }
+
+        let mut cells = vec![0; width * height];
+        for &(x, y) in alive {
+            cells[(y - min.1) as usize * width + (x - min.0) as usize] = 1;
+        }
+
+        Ok(Pattern {
+            width,
+            height,
+            cells,
+            rule,
+            origin: Some((-min.0, -min.1)),
+        })
+    }
+
+    // Mirrors the pattern left to right and or top to bottom
+    pub fn flipped(&self, horizontal: bool, vertical: bool) -> Pattern {
+        self.transformed(match (horizontal, vertical) {
+            (false, false) => Symmetry::Identity,
+            (true, false) => Symmetry::FlipX,
+            (false, true) => Symmetry::FlipY,
+            (true, true) => Symmetry::Flip,
+        })
+    }
+
+    // Turns and or mirrors the pattern, keeping its top left corner where
+    // the box around it starts
+    pub fn transformed(&self, symmetry: Symmetry) -> Pattern {
+        let [[xx, xy], [yx, yy]] = symmetry.matrix();
+        let (last_x, last_y) = (self.width as i64 - 1, self.height as i64 - 1);
+        // Moves the turned box back to start at (0, 0)
+        let shift = (
+            -(xx * last_x).min(0) - (xy * last_y).min(0),
+            -(yx * last_x).min(0) - (yy * last_y).min(0),
+        );
+        let map = |(x, y): (i64, i64)| (xx * x + xy * y + shift.0, yx * x + yy * y + shift.1);
+
+        let (width, height) = if xx == 0 {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        };
+        let mut cells = vec![0; self.cells.len()];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (to_x, to_y) = map((x as i64, y as i64));
+                cells[to_y as usize * width + to_x as usize] = self.cells[y * self.width + x];
+            }
+        }
+
+        Pattern {
+            width,
+            height,
+            cells,
+            rule: self.rule,
+            origin: self.origin.map(map),
+        }
+    }
+
+    // Crops a board with rows going down to the cells that aren't dead, or
+    // None when they all are. The middle of the board stays the origin
+    pub fn from_board(width: usize, height: usize, state: &[u8], rule: Rule) -> Option<Pattern> {
+        let alive = |x: usize, y: usize| state[y * width + x] != 0;
+        let mut rows = (0..height).filter(|&y| (0..width).any(|x| alive(x, y)));
+        let mut columns = (0..width).filter(|&x| (0..height).any(|y| alive(x, y)));
+
+        let top = rows.next()?;
+        let bottom = rows.next_back().unwrap_or(top);
+        let left = columns.next()?;
+        let right = columns.next_back().unwrap_or(left);
+
+        let cells = state
+            .chunks(width)
+            .skip(top)
+            .take(bottom - top + 1)
+            .flat_map(|row| &row[left..=right])
+            .copied()
+            .collect();
+
+        Some(Pattern {
+            width: right - left + 1,
+            height: bottom - top + 1,
+            cells,
+            rule: Some(rule),
+            origin: Some((
+                (width / 2) as i64 - left as i64,
+                (height / 2) as i64 - top as i64,
+            )),
+        })
+    }
+
+    // Writes the pattern as RLE, with multistate letters when there are more
+    // than two states
+    pub fn to_rle(&self) -> String {
+        let multistate = self.cells.iter().any(|&cell| cell > 1)
+            || self.rule.is_some_and(|rule| rule.states > 2);
+
+        let mut rle = format!("x = {}, y = {}", self.width, self.height);
+        if let Some(rule) = &self.rule {
+            rle += &format!(", rule = {}", rule);
+        }
+        rle.push('\n');
+
+        // Runs of the same token, ends of rows pile up until the next cells
+        let mut runs: Vec<(usize, String)> = Vec::new();
+        let push =
+            |runs: &mut Vec<(usize, String)>, count: usize, token: String| match runs.last_mut() {
+                Some((n, last)) if *last == token => *n += count,
+                _ => runs.push((count, token)),
+            };
+
+        for (y, row) in self.cells.chunks(self.width).enumerate() {
+            if y > 0 {
+                push(&mut runs, 1, "$".to_string());
+            }
+
+            // Dead cells at the end of a row go without saying
+            let end = row.iter().rposition(|&cell| cell != 0).map_or(0, |i| i + 1);
+            for &cell in &row[..end] {
+                push(&mut runs, 1, state_token(cell, multistate));
+            }
+        }
+        push(&mut runs, 1, "!".to_string());
+
+        // Lines are kept under 70 characters without splitting runs
+        let mut line = String::new();
+        for (count, token) in runs {
+            let run = match count {
+                1 => token,
+                n => format!("{}{}", n, token),
+            };
+            if line.len() + run.len() > 70 {
+                rle += &line;
+                rle.push('\n');
+                line.clear();
+            }
+            line += &run;
+        }
+        rle += &line;
+        rle.push('\n');
+        rle
+    }
+}
+
+fn state_token(state: u8, multistate: bool) -> String {
+    match (state, multistate) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (state, true) => {
+            let (high, low) = ((state - 1) / 24, (state - 1) % 24);
+            let letter = (b'A' + low) as char;
+            match high {
+                0 => letter.to_string(),
+                high => format!("{}{}", (b'p' + high - 1) as char, letter),
+            }
+        }
+    }
+}
+
+fn detect(text: &str, extension: Option<&str>) -> Format {
+    let first = text.lines().next().unwrap_or("").trim();
+    if first.starts_with("#Life 1.05") {
+        return Format::Life105;
+    }
+    if first.starts_with("#Life 1.06") {
+        return Format::Life106;
+    }
+    if first.starts_with("[M2]") {
+        return Format::Macrocell;
+    }
+
+    match extension.map(str::to_lowercase).as_deref() {
+        Some("rle") => return Format::Rle,
+        Some("cells") => return Format::Cells,
+        Some("mc") => return Format::Macrocell,
+        _ => {}
+    }
+
+    // Go by the first line that isn't a comment
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+        .unwrap_or("");
+
+    if text.trim_start().starts_with('!') || line.chars().all(|c| matches!(c, '.' | 'O')) {
+        Format::Cells
+    } else if parse_numbers(line).is_ok() {
+        Format::Life106
+    } else {
+        Format::Rle
+    }
+}
+
+// Two numbers separated by whitespace
+fn parse_numbers(text: &str) -> Result<(i64, i64), String> {
+    let mut numbers = text.split_whitespace().map(|n| {
+        n.parse::<i64>()
+            .map_err(|_| format!("expected a number, found '{}'", n))
+    });
+
+    match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(x), Some(y), None) => Ok((x?, y?)),
+        _ => Err(format!("expected two numbers, found '{}'", text.trim())),
+    }
+}
+
+// x = 3, y = 3, rule = B3/S23
+fn parse_header(line: &str, rule: &mut Option<Rule>) -> Result<(usize, usize), String> {
+    // Larger than Life rules have commas of their own, so the rule is
+    // everything after its '='
+    let (sizes, rule_text) = match line.find("rule") {
+        Some(i) => {
+            let value = line[i + 4..]
+                .trim_start()
+                .strip_prefix('=')
+                .ok_or("expected '=' after 'rule'")?;
+            (&line[..i], Some(value.trim()))
+        }
+        None => (line, None),
+    };
+
+    let mut size = (0, 0);
+    for part in sizes.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("expected 'key = value', found '{}'", part))?;
+        let value = value
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("expected a number, found '{}'", value.trim()))?;
+
+        match key.trim() {
+            "x" => size.0 = value,
+            "y" => size.1 = value,
+            key => return Err(format!("unknown header key '{}'", key)),
+        }
+    }
+
+    if let Some(rule_text) = rule_text {
+        *rule = Some(parse_rule(rule_text)?);
+    }
+    Ok(size)
+}
+
+pub fn parse_rule(rule: &str) -> Result<Rule, String> {
+    // Boards always fill the wallpaper, so drop sizes like the 10,10 in
+    // B3/S23:T10,10 and keep the topology
+    let rule = match rule.split_once(':') {
+        Some((kind, suffix)) => {
+            let letter = suffix.chars().next().map_or(0, char::len_utf8);
+            &rule[..kind.len() + 1 + letter]
+        }
+        None => rule,
+    };
+
+    rule.parse::<Rule>()
+        .map_err(|e| format!("couldn't read the pattern's rule: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rows of the pattern, . for dead cells and the state for others
+    fn rows(pattern: &Pattern) -> Vec<String> {
+        pattern
+            .cells
+            .chunks(pattern.width)
+            .map(|row| {
+                row.iter()
+                    .map(|&cell| match cell {
+                        0 => '.',
+                        state => char::from_digit(state as u32, 10).unwrap_or('?'),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn rle(text: &str) -> Pattern {
+        Pattern::from_rle(text).unwrap_or_else(|e| panic!("{:?} should parse: {}", text, e))
+    }
+
+    #[test]
+    fn rle_runs() {
+        let pattern = rle("x = 6, y = 1\n2o3bo!");
+        assert_eq!(rows(&pattern), ["11...1"]);
+
+        // Runs can be split over lines, and the header can be left out
+        let pattern = rle("1\n2o\n$o!");
+        assert_eq!(rows(&pattern), ["111111111111", "1..........."]);
+    }
+
+    #[test]
+    fn rle_rows() {
+        let pattern = rle("x = 3, y = 4\no2$3o$bo!");
+        assert_eq!(rows(&pattern), ["1..", "...", "111", ".1."]);
+
+        // The header can only make the pattern bigger
+        let pattern = rle("x = 4, y = 2\no!");
+        assert_eq!(rows(&pattern), ["1...", "...."]);
+    }
+
+    #[test]
+    fn rle_stops_at_bang() {
+        let pattern = rle("#N Glider\nx = 3, y = 3\nbo$2bo$3o!\n3o$3o$3o!\nnot cells");
+        assert_eq!(rows(&pattern), [".1.", "..1", "111"]);
+    }
+
+    #[test]
+    fn rle_multistate() {
+        let pattern = rle("x = 5, y = 1, rule = B2/S/C3\n.ApA2B!");
+        assert_eq!(pattern.cells, [0, 1, 25, 2, 2]);
+    }
+
+    #[test]
+    fn rle_rules() {
+        let rule = |text: &str| rle(text).rule.map(|rule| rule.to_string());
+
+        assert_eq!(
+            rule("x = 1, y = 1, rule = B36/S23\no!"),
+            Some("B36/S23".into())
+        );
+        assert_eq!(rule("x = 1, y = 1, rule = 23/3\no!"), Some("B3/S23".into()));
+        assert_eq!(rule("#r 23/36\nx = 1, y = 1\no!"), Some("B36/S23".into()));
+        assert_eq!(rule("x = 1, y = 1\no!"), None);
+        // Sizes are dropped, the board always fills the wallpaper
+        assert_eq!(
+            rule("x = 1, y = 1, rule = B3/S23:K10,10\no!"),
+            Some("B3/S23:K".into())
+        );
+        assert_eq!(
+            rule("x = 1, y = 1, rule = R2,C0,M1,S2..3,B3..3,NM\no!"),
+            Some("R2,C0,M1,S2..3,B3..3,NM".into())
+        );
+    }
+
+    #[test]
+    fn rle_errors() {
+        for (text, message) in [
+            ("x = 0, y = 0\n!", "pattern is empty"),
+            ("x = 3, y = 3\nbo$2bo&3o!", "line 2: unexpected '&'"),
+            ("pqA!", "unexpected 'q' after 'p'"),
+            (
+                "x = 2, y = 2\n.A$\n.p$B!",
+                "line 3: unexpected '$' after 'p'",
+            ),
+            ("x = 2, y = 1\n.y!", "line 2: unexpected '!' after 'y'"),
+            ("x = 2, y = 1\n.A2p", "pattern ends after 'p'"),
+            ("yXA!", "state 264 is too high"),
+            ("#N Nothing\n", "pattern is empty"),
+            ("x = 3, z = 2\no!", "unknown header key 'z'"),
+            ("x = three\no!", "expected a number"),
+            ("99999999999999999999999o!", "run is too long"),
+            ("268435457o!", "run is too long"),
+            ("16384o16385$o!", "too big"),
+            ("o268435456$o!", "too big"),
+            (
+                "x = 100000, y = 100000\no!",
+                "100000x100000 pattern is too big",
+            ),
+            (
+                "x = 1, y = 1, rule = B3/S23:é\no!",
+                "couldn't read the pattern's rule",
+            ),
+            (
+                "x = 1, y = 1, rule = B3/S23:\no!",
+                "couldn't read the pattern's rule",
+            ),
+        ] {
+            match Pattern::from_rle(text) {
+                Ok(pattern) => panic!("{:?} parsed as {:?}", text, rows(&pattern)),
+                Err(e) => assert!(e.contains(message), "{:?}: {}", text, e),
+            }
+        }
+    }
+
+    #[test]
+    fn plaintext() {
+        let pattern = Pattern::parse("!Name: Glider\n!\n.O\n..O\nOO*\n\n", None).unwrap();
+        assert_eq!(rows(&pattern), [".1.", "..1", "111"]);
+        assert_eq!((pattern.rule, pattern.origin), (None, None));
+
+        let error = Pattern::from_plaintext("!Bad\n.O.\n.o.\n").unwrap_err();
+        assert_eq!(error, "line 3: unexpected 'o'");
+        assert!(Pattern::from_plaintext("!Nothing\n\n").is_err());
+    }
+
+    #[test]
+    fn life_105() {
+        let text = "#Life 1.05\n#D Glider\n#N\n#P -1 -1\n.*.\n..*\n***\n";
+        let pattern = Pattern::parse(text, None).unwrap();
+        assert_eq!(rows(&pattern), [".1.", "..1", "111"]);
+        assert_eq!(pattern.rule, Some("B3/S23".parse().unwrap()));
+        assert_eq!(pattern.origin, Some((1, 1)));
+
+        // Blocks far from the origin, which stays where it is
+        let text = "#Life 1.05\n#R 23/36\n#P 10 20\n**\n#P 13 21\n*\n";
+        let pattern = Pattern::parse(text, None).unwrap();
+        assert_eq!(rows(&pattern), ["11..", "...1"]);
+        assert_eq!(pattern.rule, Some("B36/S23".parse().unwrap()));
+        assert_eq!(pattern.origin, Some((-10, -20)));
+
+        // Tags it doesn't know are skipped, whatever they start with
+        let text = "#Life 1.05\n#é\n#\n#P 0 0\n*\n";
+        assert_eq!(rows(&Pattern::parse(text, None).unwrap()), ["1"]);
+
+        let error = Pattern::from_life_105("#Life 1.05\n#P 0\n*\n").unwrap_err();
+        assert_eq!(error, "line 2: expected two numbers, found '0'");
+        let error = Pattern::from_life_105("#Life 1.05\n#P 0 0\n*x\n").unwrap_err();
+        assert_eq!(error, "line 3: unexpected 'x'");
+    }
+
+    #[test]
+    fn life_106() {
+        let text = "#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n";
+        let pattern = Pattern::parse(text, None).unwrap();
+        assert_eq!(rows(&pattern), [".1.", "..1", "111"]);
+        assert_eq!(pattern.origin, Some((1, 1)));
+
+        // Also without the header, and with the origin outside of the cells
+        let pattern = Pattern::parse("-5 -7\n-4 -7\n", None).unwrap();
+        assert_eq!(rows(&pattern), ["11"]);
+        assert_eq!(pattern.origin, Some((5, 7)));
+
+        let error = Pattern::from_life_106("#Life 1.06\n0 0\n1 x\n").unwrap_err();
+        assert_eq!(error, "line 3: expected a number, found 'x'");
+        assert!(Pattern::from_life_106("#Life 1.06\n").is_err());
+    }
+
+    #[test]
+    fn origin_follows_transforms() {
+        let pattern = Pattern::from_life_106("#Life 1.06\n3 0\n4 0\n4 1\n4 2\n").unwrap();
+        assert_eq!(rows(&pattern), ["11", ".1", ".1"]);
+        assert_eq!(pattern.origin, Some((-3, 0)));
+
+        let turned = pattern.transformed(Symmetry::Rcw);
+        assert_eq!(rows(&turned), ["..1", "111"]);
+        assert_eq!(turned.origin, Some((2, -3)));
+
+        // The origin stays on the same cell, relative to the others
+        for symmetry in Symmetry::ALL {
+            let turned = pattern.transformed(symmetry);
+            let back = Symmetry::ALL
+                .into_iter()
+                .find(|&inverse| turned.transformed(inverse).cells == pattern.cells)
+                .unwrap();
+            assert_eq!(turned.transformed(back), pattern, "{}", symmetry);
+        }
+    }
+
+    #[test]
+    fn board_origin_is_its_middle() {
+        let mut state = vec![0; 8 * 6];
+        state[8 + 6] = 1;
+        state[2 * 8 + 7] = 1;
+        let pattern = Pattern::from_board(8, 6, &state, "B3/S23".parse().unwrap()).unwrap();
+        assert_eq!(rows(&pattern), ["1.", ".1"]);
+        assert_eq!(pattern.origin, Some((-2, 2)));
+
+        assert_eq!(
+            Pattern::from_board(8, 6, &[0; 48], "B3/S23".parse().unwrap()),
+            None
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use std::path::{Path, PathBuf};
+
+use gumdrop::Options;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::hashlife::HashLife;
+use crate::image::{write_png, write_ppm};
+use crate::rule::Rule;
+use crate::simulator::{Engine, Region, Simulator};
+use crate::software::pixel_cells;
+use crate::{offscreen_context, Colors, GpuGoL, Size, Start};
+
+#[derive(Debug, Options)]
+pub struct RenderOptions {
+    #[options(help = "print help message")]
+    help: bool,
+
+    #[options(
+        help = "Generations to run, or the most to wait for with --until-stable",
+        default = "500",
+        no_short
+    )]
+    gens: u64,
+
+    #[options(
+        help = "Generations between frames, 0 for only the last one",
+        default = "10",
+        no_short
+    )]
+    every: u64,
+
+    #[options(
+        help = "Where frames go, .png or .ppm, or - to print them as text. A %d or %05d in it is replaced with the frame number",
+        default = "frames/%05d.png",
+        no_short
+    )]
+    out: String,
+
+    #[options(help = "Chance of a cell starting alive", default = "0.5", no_short)]
+    density: f64,
+
+    #[options(
+        help = "Let the board loose on an unbounded plane until its population repeats, like a methuselah settling down. Needs --engine hashlife",
+        no_short
+    )]
+    until_stable: bool,
+}
+
+// Longest period the population of a settled pattern is checked for, and
+// how long it has to keep repeating
+const STABLE_PERIOD: usize = 30;
+const STABLE_FOR: u64 = 1000;
+// Generations from one look at the population to the next while it's still
+// changing, jumped over. A look steps through two of the longest periods and
+// has to fit in any STABLE_FOR generations
+const LOOK_EVERY: u64 = 512;
+
+// What the frames are drawn like, from the wallpaper options
+pub struct Look {
+    pub size: Size,
+    pub scale: u32,
+    pub rule: Rule,
+    pub colors: Colors,
+    pub engine: Engine,
+    pub seed: u64,
+    // Started from instead of a random board, when there is anything
+    pub start: Start,
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Png,
+    Ppm,
+    Text,
+}
+
+// Runs a random board and writes frames of it as the wallpaper would show
+// them, without opening a window
+pub fn render(opts: RenderOptions, look: Look) -> Result<(), String> {
+    let format = match Path::new(&opts.out).extension().and_then(|e| e.to_str()) {
+        _ if opts.out == "-" => Format::Text,
+        Some(e) if e.eq_ignore_ascii_case("png") => Format::Png,
+        Some(e) if e.eq_ignore_ascii_case("ppm") => Format::Ppm,
+        _ => {
+            return Err(format!(
+                "Can't tell the format of '{}', expected .png, .ppm or -",
+                opts.out
+            ))
+        }
+    };
+
+    let generations = if opts.every == 0 {
+        vec![opts.gens]
+    } else {
+        let mut generations = (0..=opts.gens)
+            .step_by(opts.every as usize)
+            .collect::<Vec<u64>>();
+        if generations.last() != Some(&opts.gens) {
+            generations.push(opts.gens);
+        }
+        generations
+    };
+    if generations.len() > 1 && !opts.out.contains('%') && opts.out != "-" {
+        return Err("Writing more than one frame needs a %d in --out".to_string());
+    }
+
+    let Size { width, height } = look.size;
+    let (board_width, board_height) = (width / look.scale as usize, height / look.scale as usize);
+
+    let cells = pixel_cells((width, height), (board_width, board_height), &look.rule);
+    let palette = look.colors.palette(look.rule.states);
+
+    let mut frame = 0;
+    let mut draw = |generation: u64, board: &dyn Simulator| -> Result<(), String> {
+        let write = match format {
+            Format::Png => write_png,
+            Format::Ppm => write_ppm,
+            Format::Text => {
+                println!("Generation {}", generation);
+                board.print(".", "O");
+                return Ok(());
+            }
+        };
+
+        let state = board.read_state();
+        let pixels = cells
+            .iter()
+            .map(|cell| match cell {
+                // Pattern states the rule doesn't have are drawn dead
+                Some(cell) => *palette
+                    .get(state[*cell as usize] as usize)
+                    .unwrap_or(&palette[0]),
+                None => palette[0],
+            })
+            .collect::<Vec<_>>();
+
+        let path = frame_path(&opts.out, frame);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        write(&path, width, height, &pixels)
+            .map_err(|e| format!("Couldn't write {}: {}", path.display(), e))?;
+
+        println!("Generation {} -> {}", generation, path.display());
+        frame += 1;
+        Ok(())
+    };
+
+    if opts.until_stable {
+        if look.engine != Engine::HashLife {
+            return Err(
+                "--until-stable runs on an unbounded plane, it needs --engine hashlife".into(),
+            );
+        }
+
+        // Laid out on a board like any other start, which then becomes the
+        // window the plane is drawn through
+        let mut board = Engine::Basic.new_cpu(board_width, board_height, &look.rule)?;
+        set_up(board.as_mut(), &opts, &look);
+        let mut life =
+            HashLife::from_cells(&look.rule, board_width, board_height, &board.read_state())?;
+
+        if opts.every != 0 {
+            draw(0, board.as_ref())?;
+        }
+
+        let mut draw_life = |life: &HashLife| {
+            let cells = life.to_cells(0, 0, board_width, board_height);
+            board.set_cells(Region::new(0, 0, board_width, board_height), &cells);
+            draw(life.generation(), board.as_ref())
+        };
+        if let Some((start, period)) = settle(&mut life, opts.gens, opts.every, &mut draw_life)? {
+            // Drawn already when it settled on a frame
+            let generation = life.generation();
+            if opts.every == 0 || generation % opts.every != 0 {
+                draw_life(&life)?;
+            }
+            println!(
+                "Stable from generation {}, period {}, population {}",
+                start,
+                period,
+                life.population()
+            );
+            return Ok(());
+        }
+
+        return Err(format!(
+            "Population still changing after {} generations",
+            opts.gens
+        ));
+    }
+
+    // The GPU engine runs in a hidden window, which has to outlive it
+    let _context;
+    let mut engine: Box<dyn Simulator> = match look.engine {
+        Engine::Gpu => {
+            _context = offscreen_context(board_width as u32, board_height as u32)?;
+            Box::new(GpuGoL::new(
+                board_width as u32,
+                board_height as u32,
+                &look.rule,
+            ))
+        }
+        engine => engine.new_cpu(board_width, board_height, &look.rule)?,
+    };
+    set_up(engine.as_mut(), &opts, &look);
+
+    let mut generation = 0;
+    for &target in &generations {
+        engine.tick(target - generation);
+        generation = target;
+        draw(generation, engine.as_ref())?;
+    }
+
+    Ok(())
+}
+
+// Runs the plane until its population has repeated with a period of at most
+// STABLE_PERIOD for STABLE_FOR generations, drawing every `every` generations.
+// Returns the generation it repeats from and the period, or None if it still
+// changes after `until` generations.
+//
+// While the population changes, the plane jumps LOOK_EVERY generations at a
+// time and is only looked at for a few periods after each jump. Once a look
+// finds it repeating, it goes back to the look before and steps one
+// generation at a time from there, which finds the same generation as
+// stepping one at a time from the start
+fn settle(
+    life: &mut HashLife,
+    until: u64,
+    every: u64,
+    draw: &mut impl FnMut(&HashLife) -> Result<(), String>,
+) -> Result<Option<(u64, usize)>, String> {
+    // Going back passes frames again
+    let mut drawn = life.generation();
+    let mut advance = |life: &mut HashLife, generations: u64| {
+        let target = life.generation() + generations;
+        while life.generation() < target {
+            let next = match every {
+                0 => target,
+                every => (life.generation() / every + 1) * every,
+            };
+            life.step(next.min(target) - life.generation());
+
+            if every != 0 && life.generation().is_multiple_of(every) && life.generation() > drawn {
+                drawn = life.generation();
+                draw(life)?;
+            }
+        }
+        Ok::<(), String>(())
+    };
+
+    let look = 2 * STABLE_PERIOD as u64;
+    let mut before = life.snapshot();
+    loop {
+        if life.generation() + look > until {
+            return Ok(None);
+        }
+
+        let snapshot = life.snapshot();
+        let mut populations = vec![life.population()];
+        for _ in 0..look {
+            advance(life, 1)?;
+            populations.push(life.population());
+        }
+        let repeats = (1..=STABLE_PERIOD)
+            .any(|period| populations.windows(period + 1).all(|w| w[0] == w[period]));
+        if repeats {
+            break;
+        }
+
+        before = snapshot;
+        advance(life, LOOK_EVERY - look)?;
+    }
+
+    life.restore(before);
+    let start = life.generation();
+    let mut populations = vec![life.population()];
+    // Generation from which the population has repeated, by period
+    let mut since = [0; STABLE_PERIOD + 1];
+
+    while life.generation() < until {
+        advance(life, 1)?;
+        let generation = life.generation();
+        let population = life.population();
+        populations.push(population);
+
+        for (period, since) in since.iter_mut().enumerate().skip(1) {
+            if generation < start + period as u64
+                || populations[(generation - start) as usize - period] != population
+            {
+                *since = generation + 1;
+            }
+        }
+        // Repeating since generation g with period p means stable from g - p
+        let settled = since
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, &since)| generation >= since + STABLE_FOR)
+            .map(|(period, &since)| (since - period as u64, period))
+            .min();
+        if settled.is_some() {
+            return Ok(settled);
+        }
+    }
+
+    Ok(None)
+}
+
+fn set_up(engine: &mut dyn Simulator, opts: &RenderOptions, look: &Look) {
+    if look.start.is_empty() {
+        let mut rng = StdRng::seed_from_u64(look.seed);
+        engine.randomize(opts.density, rng.gen());
+    } else {
+        look.start.apply(engine);
+    }
+}
+
+// Replaces the first %d, or %0Nd, in the pattern with the frame number
+fn frame_path(pattern: &str, frame: usize) -> PathBuf {
+    let Some(start) = pattern.find('%') else {
+        return PathBuf::from(pattern);
+    };
+    let Some(end) = pattern[start..].find('d').map(|i| start + i) else {
+        return PathBuf::from(pattern);
+    };
+
+    let width = pattern[start + 1..end].parse::<usize>().unwrap_or(0);
+    PathBuf::from(format!(
+        "{}{:0width$}{}",
+        &pattern[..start],
+        frame,
+        &pattern[end + 1..],
+        width = width
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library;
+
+    fn library(name: &str) -> HashLife {
+        let pattern = library::find(name).unwrap().pattern();
+        let rule = "B3/S23".parse().unwrap();
+        HashLife::from_cells(&rule, pattern.width, pattern.height, &pattern.cells).unwrap()
+    }
+
+    #[test]
+    fn methuselahs_settle() {
+        for (name, settled) in [("acorn", 5206), ("r-pentomino", 1103), ("diehard", 130)] {
+            let mut life = library(name);
+            let mut frames = vec![];
+            let mut draw = |life: &HashLife| {
+                frames.push(life.generation());
+                Ok(())
+            };
+
+            assert_eq!(
+                settle(&mut life, 7000, 500, &mut draw),
+                Ok(Some((settled, 1))),
+                "{}",
+                name
+            );
+            // Found once the population kept still for long enough
+            let found = settled + 1 + STABLE_FOR;
+            assert_eq!(life.generation(), found);
+            // Every frame up to then, once, even after going back
+            let every = (500..=found).step_by(500).collect::<Vec<u64>>();
+            assert_eq!(frames, every, "{}", name);
+        }
+    }
+
+    #[test]
+    fn gives_up() {
+        // Settles at 1103, found at 2104
+        let mut life = library("r-pentomino");
+        assert_eq!(settle(&mut life, 2100, 0, &mut |_| Ok(())), Ok(None));
+        assert!(life.generation() <= 2100);
+    }
+
+    #[test]
+    fn frame_paths() {
+        assert_eq!(
+            frame_path("frames/%d.png", 7),
+            PathBuf::from("frames/7.png")
+        );
+        assert_eq!(
+            frame_path("frames/%d.png", 1234),
+            PathBuf::from("frames/1234.png")
+        );
+        assert_eq!(
+            frame_path("frames/%05d.png", 7),
+            PathBuf::from("frames/00007.png")
+        );
+        // Numbers wider than the padding aren't cut
+        assert_eq!(
+            frame_path("frames/%05d.png", 123456),
+            PathBuf::from("frames/123456.png")
+        );
+        // Only the first one is replaced
+        assert_eq!(frame_path("%d/%d.ppm", 3), PathBuf::from("3/%d.ppm"));
+        assert_eq!(frame_path("last.png", 7), PathBuf::from("last.png"));
+    }
+}
@@ -0,0 +1,1194 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Set of neighbor configurations that make a transition happen. A
+// configuration has one bit per neighbor:
+//
+//   0x80 0x40 0x20
+//   0x10  ..  0x08
+//   0x04 0x02 0x01
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Transitions(pub [u32; 8]);
+
+impl Transitions {
+    pub fn contains(&self, config: u8) -> bool {
+        self.0[config as usize >> 5] >> (config & 31) & 1 == 1
+    }
+
+    // The neighbor counts that make the transition happen, one bit per
+    // count, or None if it depends on more than the number of neighbors in
+    // `mask` that are alive
+    pub fn counts(&self, mask: u8) -> Option<u16> {
+        let mut counts = 0u16;
+        let mut seen = 0u16;
+
+        for config in 0..=255u8 {
+            let bit = 1 << (config & mask).count_ones();
+            let value = self.contains(config);
+            if seen & bit != 0 && (counts & bit != 0) != value {
+                return None;
+            }
+
+            seen |= bit;
+            if value {
+                counts |= bit;
+            }
+        }
+
+        Some(counts)
+    }
+
+    fn set(&mut self, config: u8, value: bool) {
+        let word = &mut self.0[config as usize >> 5];
+        if value {
+            *word |= 1 << (config & 31);
+        } else {
+            *word &= !(1 << (config & 31));
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub kind: RuleKind,
+    // Generations rules have dying states between alive (1) and dead (0),
+    // plain life-like rules have 2 states
+    pub states: u32,
+    pub topology: Topology,
+}
+
+// How the edges of the board are joined, as in Golly's :T, :P, :K and :C
+// suffixes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Topology {
+    #[default]
+    Torus,
+    // Everything past the edges is dead
+    Plane,
+    // A torus with the top and bottom edges joined with a twist, so crossing
+    // them mirrors the x coordinate
+    KleinBottle,
+    // Both pairs of edges joined with a twist
+    CrossSurface,
+}
+
+impl Topology {
+    // Maps a cell outside of the board to the cell it stands for, or None if
+    // it is past the edge of a plane
+    pub fn wrap(self, x: i64, y: i64, width: i64, height: i64) -> Option<(i64, i64)> {
+        let (qx, qy) = (x.div_euclid(width), y.div_euclid(height));
+        let (mut x, mut y) = (x.rem_euclid(width), y.rem_euclid(height));
+
+        match self {
+            Topology::Torus => {}
+            Topology::Plane => {
+                if (qx, qy) != (0, 0) {
+                    return None;
+                }
+            }
+            Topology::KleinBottle | Topology::CrossSurface => {
+                if qy % 2 != 0 {
+                    x = width - 1 - x;
+                }
+                if self == Topology::CrossSurface && qx % 2 != 0 {
+                    y = height - 1 - y;
+                }
+            }
+        }
+
+        Some((x, y))
+    }
+
+    fn letter(self) -> char {
+        match self {
+            Topology::Torus => 'T',
+            Topology::Plane => 'P',
+            Topology::KleinBottle => 'K',
+            Topology::CrossSurface => 'C',
+        }
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "t" | "torus" => Ok(Topology::Torus),
+            "p" | "plane" => Ok(Topology::Plane),
+            "k" | "klein" => Ok(Topology::KleinBottle),
+            "c" | "cross" => Ok(Topology::CrossSurface),
+            _ => Err(format!(
+                "unknown topology \"{}\", expected T, P, K or C",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+    // Range 1 rules, decided by which of the 8 neighbors are alive. Neighbors
+    // outside of the neighborhood never change the outcome
+    Life {
+        birth: Transitions,
+        survive: Transitions,
+        neighborhood: Neighborhood,
+    },
+    // Range R rules, decided by how many cells in the neighborhood are alive
+    LargerThanLife(Ltl),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighborhood {
+    Moore,
+    // Only N, W, E and S
+    VonNeumann,
+    // Everything but NE and SW, which makes the grid a skewed hexagonal one
+    Hexagonal,
+}
+
+impl Neighborhood {
+    // Configuration bits of the neighbors that are part of the neighborhood
+    pub fn mask(self) -> u8 {
+        match self {
+            Neighborhood::Moore => 0xFF,
+            Neighborhood::VonNeumann => 0x5A,
+            Neighborhood::Hexagonal => 0xDB,
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            Neighborhood::Moore => "",
+            Neighborhood::VonNeumann => "V",
+            Neighborhood::Hexagonal => "H",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Neighborhood::Moore => "Moore",
+            Neighborhood::VonNeumann => "von Neumann",
+            Neighborhood::Hexagonal => "hexagonal",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ltl {
+    pub range: u32,
+    pub shape: Shape,
+    // Whether the cell itself counts towards its neighbors
+    pub middle: bool,
+    // Inclusive bounds on the neighbor count
+    pub birth: (u32, u32),
+    pub survive: (u32, u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Moore,
+    VonNeumann,
+    Circular,
+}
+
+pub const MAX_STATES: u32 = 256;
+pub const MAX_RANGE: u32 = 50;
+
+impl Rule {
+    pub fn is_hexagonal(&self) -> bool {
+        matches!(
+            self.kind,
+            RuleKind::Life {
+                neighborhood: Neighborhood::Hexagonal,
+                ..
+            }
+        )
+    }
+
+    // How far the neighborhood reaches from the cell in any direction
+    pub fn range(&self) -> u32 {
+        match &self.kind {
+            RuleKind::Life { .. } => 1,
+            RuleKind::LargerThanLife(ltl) => ltl.range,
+        }
+    }
+
+    // State of a cell on the next tick, given whether the neighbors would make
+    // a dead cell be born and an alive one survive. Same as the gol shader
+    pub fn next_state(&self, current: u8, born: bool, survives: bool) -> u8 {
+        match current {
+            0 => born as u8,
+            // Cells that fail to survive start dying
+            1 if survives => 1,
+            1 => (2 % self.states) as u8,
+            // States left over from a rule with more of them die out right away
+            _ if (current as u32) + 1 < self.states => current + 1,
+            _ => 0,
+        }
+    }
+}
+
+impl Shape {
+    // How far the neighborhood reaches left and right on the row `dy` away
+    // from the center, or None if it does not reach that row at all
+    pub fn half_width(self, range: u32, dy: i32) -> Option<u32> {
+        let dy = dy.unsigned_abs();
+        if dy > range {
+            return None;
+        }
+
+        Some(match self {
+            Shape::Moore => range,
+            Shape::VonNeumann => range - dy,
+            // Cells within range + 1/2 of the center
+            Shape::Circular => {
+                let limit = range * range + range - dy * dy;
+                (0..=range)
+                    .take_while(|dx| dx * dx <= limit)
+                    .last()
+                    .unwrap()
+            }
+        })
+    }
+
+    fn letter(self) -> char {
+        match self {
+            Shape::Moore => 'M',
+            Shape::VonNeumann => 'N',
+            Shape::Circular => 'C',
+        }
+    }
+}
+
+impl Ltl {
+    // Number of cells in the neighborhood, including the cell itself
+    pub fn size(&self) -> u32 {
+        let range = self.range as i32;
+        (-range..=range)
+            .filter_map(|dy| self.shape.half_width(self.range, dy))
+            .map(|w| 2 * w + 1)
+            .sum()
+    }
+
+    pub fn max_count(&self) -> u32 {
+        self.size() - !self.middle as u32
+    }
+}
+
+// Hensel notation letters for every neighbor count, in canonical order, each
+// with one of the configurations it stands for. The rest are its rotations
+// and reflections.
+const HENSEL: [&[(char, u8)]; 9] = [
+    &[],
+    &[('c', 0x01), ('e', 0x02)],
+    &[
+        ('c', 0x05),
+        ('e', 0x0a),
+        ('k', 0x0c),
+        ('a', 0x03),
+        ('i', 0x18),
+        ('n', 0x24),
+    ],
+    &[
+        ('c', 0x25),
+        ('e', 0x1a),
+        ('k', 0x32),
+        ('a', 0x0b),
+        ('i', 0x07),
+        ('n', 0x0d),
+        ('y', 0x31),
+        ('q', 0x26),
+        ('j', 0x0e),
+        ('r', 0x19),
+    ],
+    &[
+        ('c', 0xa5),
+        ('e', 0x5a),
+        ('k', 0x33),
+        ('a', 0x0f),
+        ('i', 0x1d),
+        ('n', 0x27),
+        ('y', 0x35),
+        ('q', 0x36),
+        ('j', 0x3a),
+        ('r', 0x1b),
+        ('t', 0x39),
+        ('w', 0x2e),
+        ('z', 0x3c),
+    ],
+    &[
+        ('c', 0x5b),
+        ('e', 0xa7),
+        ('k', 0x75),
+        ('a', 0x2f),
+        ('i', 0x1f),
+        ('n', 0x3b),
+        ('y', 0x5d),
+        ('q', 0x3e),
+        ('j', 0x37),
+        ('r', 0x3d),
+    ],
+    &[
+        ('c', 0x5f),
+        ('e', 0xaf),
+        ('k', 0x77),
+        ('a', 0x3f),
+        ('i', 0xbd),
+        ('n', 0x7e),
+    ],
+    &[('c', 0x7f), ('e', 0xbf)],
+    &[],
+];
+
+// Neighbor offsets (x right, y down) in bit order, most significant first
+pub const NEIGHBORS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+fn neighbor_bit(x: i32, y: i32) -> u8 {
+    let i = NEIGHBORS.iter().position(|&n| n == (x, y)).unwrap();
+    0x80 >> i
+}
+
+type Transform = fn(i32, i32) -> (i32, i32);
+
+// Every configuration the given one can be rotated or reflected into
+fn symmetries(config: u8) -> impl Iterator<Item = u8> {
+    let transforms: [Transform; 8] = [
+        |x, y| (x, y),
+        |x, y| (-y, x),
+        |x, y| (-x, -y),
+        |x, y| (y, -x),
+        |x, y| (-x, y),
+        |x, y| (x, -y),
+        |x, y| (y, x),
+        |x, y| (-y, -x),
+    ];
+
+    transforms.into_iter().map(move |t| {
+        NEIGHBORS
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| config & (0x80 >> i) != 0)
+            .fold(0, |acc, (_, &(x, y))| {
+                let (x, y) = t(x, y);
+                acc | neighbor_bit(x, y)
+            })
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleError {
+    pub rule: String,
+    // Byte offset into `rule` where the problem was found
+    pub position: usize,
+    pub message: String,
+}
+
+impl RuleError {
+    fn new(rule: &str, position: usize, message: impl Into<String>) -> Self {
+        RuleError {
+            rule: rule.to_string(),
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid rule \"{}\": {} at position {}",
+            self.rule, self.message, self.position
+        )
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        Parser::new(rule).parse()
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_kind(f)?;
+        if self.topology != Topology::Torus {
+            write!(f, ":{}", self.topology.letter())?;
+        }
+        Ok(())
+    }
+}
+
+impl Rule {
+    fn fmt_kind(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            RuleKind::Life {
+                birth,
+                survive,
+                neighborhood,
+            } => {
+                write!(f, "B")?;
+                write_transitions(f, birth, *neighborhood)?;
+                write!(f, "/S")?;
+                write_transitions(f, survive, *neighborhood)?;
+                if self.states > 2 {
+                    write!(f, "/C{}", self.states)?;
+                }
+                write!(f, "{}", neighborhood.suffix())
+            }
+            RuleKind::LargerThanLife(ltl) => write!(
+                f,
+                "R{},C{},M{},S{}..{},B{}..{},N{}",
+                ltl.range,
+                if self.states > 2 { self.states } else { 0 },
+                ltl.middle as u32,
+                ltl.survive.0,
+                ltl.survive.1,
+                ltl.birth.0,
+                ltl.birth.1,
+                ltl.shape.letter()
+            ),
+        }
+    }
+}
+
+impl fmt::Debug for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Rule").field(&self.to_string()).finish()
+    }
+}
+
+fn write_transitions(
+    f: &mut fmt::Formatter,
+    table: &Transitions,
+    neighborhood: Neighborhood,
+) -> fmt::Result {
+    if neighborhood != Neighborhood::Moore {
+        // Only plain counts, made of the lowest neighbors in the neighborhood
+        let mask = neighborhood.mask();
+        for n in 0..=mask.count_ones() {
+            let config = (0..8)
+                .map(|i| 1u8 << i)
+                .filter(|bit| mask & bit != 0)
+                .take(n as usize)
+                .fold(0, |acc, bit| acc | bit);
+            if table.contains(config) {
+                write!(f, "{}", n)?;
+            }
+        }
+        return Ok(());
+    }
+
+    for (n, letters) in HENSEL.iter().enumerate() {
+        let (with, without): (Vec<_>, Vec<_>) = letters
+            .iter()
+            .partition(|&&(_, config)| table.contains(config));
+
+        if letters.is_empty() {
+            if table.contains((0xFFu16 >> (8 - n)) as u8) {
+                write!(f, "{}", n)?;
+            }
+        } else if without.is_empty() {
+            write!(f, "{}", n)?;
+        } else if with.is_empty() {
+            // Count not in the rule at all
+        } else if with.len() <= without.len() {
+            write!(f, "{}", n)?;
+            for (letter, _) in with {
+                write!(f, "{}", letter)?;
+            }
+        } else {
+            write!(f, "{}-", n)?;
+            for (letter, _) in without {
+                write!(f, "{}", letter)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Parser<'a> {
+    rule: &'a str,
+    // Non whitespace characters, uppercased, with their byte offsets
+    chars: Vec<(usize, char)>,
+    next: usize,
+    // Every count parsed so far, with its position and whether it had Hensel
+    // letters, to check them once the neighborhood is known
+    counts: Vec<(usize, u32, bool)>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(rule: &'a str) -> Self {
+        let chars = rule
+            .char_indices()
+            .filter(|(_, c)| !c.is_whitespace())
+            .map(|(i, c)| (i, c.to_ascii_uppercase()))
+            .collect();
+
+        Parser {
+            rule,
+            chars,
+            next: 0,
+            counts: Vec::new(),
+        }
+    }
+
+    fn error(&self, position: usize, message: impl Into<String>) -> RuleError {
+        RuleError::new(self.rule, position, message)
+    }
+
+    fn peek(&self) -> Option<(usize, char)> {
+        self.chars.get(self.next).copied()
+    }
+
+    fn bump(&mut self) -> Option<(usize, char)> {
+        let c = self.peek();
+        self.next += 1;
+        c
+    }
+
+    fn end(&self) -> usize {
+        self.rule.len()
+    }
+
+    fn parse(mut self) -> Result<Rule, RuleError> {
+        // Split off the topology first so the rest ends before the ':'
+        let topology = match self.chars.iter().position(|&(_, c)| c == ':') {
+            Some(i) => {
+                let suffix = self.chars.split_off(i);
+                self.parse_topology(&suffix)?
+            }
+            None => Topology::Torus,
+        };
+
+        let mut rule = match self.peek() {
+            None => Err(self.error(0, "rule is empty")),
+            Some((_, 'B')) | Some((_, 'S')) => self.parse_prefixed(),
+            Some((_, 'R')) => self.parse_ltl(),
+            Some(_) => self.parse_survive_birth(),
+        }?;
+
+        rule.topology = topology;
+        Ok(rule)
+    }
+
+    // :T, :P, :K or :C
+    fn parse_topology(&self, suffix: &[(usize, char)]) -> Result<Topology, RuleError> {
+        let (pos, letter) = match suffix.get(1) {
+            Some(&c) => c,
+            None => return Err(self.error(suffix[0].0, "expected topology after ':'")),
+        };
+
+        let topology = match letter {
+            'T' | 'P' | 'K' | 'C' => letter.to_string().parse().unwrap(),
+            _ => {
+                return Err(self.error(
+                    pos,
+                    format!("expected topology 'T', 'P', 'K' or 'C', found '{}'", letter),
+                ));
+            }
+        };
+
+        // Golly's board size, WIDTH,HEIGHT with 0 for no fixed size. The
+        // board always fills the wallpaper, so zeros are all there can be
+        if let Some(&(pos, _)) = suffix.get(2) {
+            let parts = suffix[2..].split(|&(_, c)| c == ',').collect::<Vec<_>>();
+            let number = |part: &&[(usize, char)]| {
+                !part.is_empty() && part.iter().all(|&(_, c)| c.is_ascii_digit())
+            };
+            if parts.len() > 2 || !parts.iter().all(number) {
+                return Err(self.error(pos, "expected a board size like 0,0"));
+            }
+
+            if let Some(&(pos, _)) = suffix[2..].iter().find(|&&(_, c)| c != '0' && c != ',') {
+                return Err(self.error(
+                    pos,
+                    "board sizes are not supported, the board always fills the wallpaper",
+                ));
+            }
+        }
+
+        Ok(topology)
+    }
+
+    // B3/S23, b3s23, S23/B3, B2/S/C3, B2/S34H
+    fn parse_prefixed(&mut self) -> Result<Rule, RuleError> {
+        let mut birth = None;
+        let mut survive = None;
+        let mut states = None;
+        let mut neighborhood = Neighborhood::Moore;
+
+        while let Some((pos, c)) = self.bump() {
+            match c {
+                'B' | 'S' => {
+                    let section = if c == 'B' { &mut birth } else { &mut survive };
+                    if section.is_some() {
+                        return Err(self.error(pos, format!("'{}' section given twice", c)));
+                    }
+                    *section = Some(self.parse_counts()?);
+                }
+                'C' | 'G' => {
+                    if states.is_some() {
+                        return Err(self.error(pos, "number of states given twice"));
+                    }
+                    states = Some(self.parse_states()?);
+                }
+                'H' | 'V' => {
+                    neighborhood = if c == 'H' {
+                        Neighborhood::Hexagonal
+                    } else {
+                        Neighborhood::VonNeumann
+                    };
+                    if let Some((pos, c)) = self.peek() {
+                        return Err(
+                            self.error(pos, format!("unexpected '{}' after the neighborhood", c))
+                        );
+                    }
+                }
+                _ => {
+                    return Err(self.error(pos, format!("expected 'B', 'S' or 'C', found '{}'", c)));
+                }
+            }
+
+            if let Some((pos, c)) = self.peek() {
+                if c == '/' {
+                    self.bump();
+                    if self.peek().is_none() {
+                        return Err(self.error(pos, "trailing '/'"));
+                    }
+                }
+            }
+        }
+
+        match (birth, survive) {
+            (Some(birth), Some(survive)) => {
+                self.life_rule(birth, survive, states.unwrap_or(2), neighborhood)
+            }
+            (None, _) => Err(self.error(self.end(), "missing 'B' section")),
+            (_, None) => Err(self.error(self.end(), "missing 'S' section")),
+        }
+    }
+
+    // 23/3, 345/2/4, 1/1V
+    fn parse_survive_birth(&mut self) -> Result<Rule, RuleError> {
+        let survive = self.parse_counts()?;
+
+        match self.bump() {
+            Some((_, '/')) => {}
+            Some((pos, c)) => return Err(self.error(pos, format!("expected '/', found '{}'", c))),
+            None => return Err(self.error(self.end(), "expected '/' between S and B")),
+        }
+
+        let birth = self.parse_counts()?;
+
+        let states = match self.peek() {
+            Some((_, '/')) => {
+                self.bump();
+                self.parse_states()?
+            }
+            _ => 2,
+        };
+
+        let neighborhood = match self.peek() {
+            Some((_, 'H')) => Neighborhood::Hexagonal,
+            Some((_, 'V')) => Neighborhood::VonNeumann,
+            _ => Neighborhood::Moore,
+        };
+        if neighborhood != Neighborhood::Moore {
+            self.bump();
+        }
+
+        if let Some((pos, c)) = self.peek() {
+            return Err(self.error(pos, format!("unexpected '{}'", c)));
+        }
+
+        self.life_rule(birth, survive, states, neighborhood)
+    }
+
+    // Checks the counts against the neighborhood and drops the neighbors that
+    // are not part of it from the transitions
+    fn life_rule(
+        &self,
+        birth: Transitions,
+        survive: Transitions,
+        states: u32,
+        neighborhood: Neighborhood,
+    ) -> Result<Rule, RuleError> {
+        let mask = neighborhood.mask();
+        let (birth, survive) = if neighborhood == Neighborhood::Moore {
+            (birth, survive)
+        } else {
+            let max = mask.count_ones();
+            for &(pos, n, letters) in &self.counts {
+                if letters {
+                    return Err(
+                        self.error(pos, "Hensel letters only work with the Moore neighborhood")
+                    );
+                }
+                if n > max {
+                    return Err(self.error(
+                        pos,
+                        format!(
+                            "{} is more than the {} neighbors of the {} neighborhood",
+                            n,
+                            max,
+                            neighborhood.name()
+                        ),
+                    ));
+                }
+            }
+
+            // Without letters the tables are totalistic, so any configuration
+            // with the same count stands for all of them
+            let restrict = |table: Transitions| {
+                let mut restricted = Transitions::default();
+                for config in 0..=255u8 {
+                    let n = (config & mask).count_ones();
+                    restricted.set(config, table.contains((0xFFu16 >> (8 - n)) as u8));
+                }
+                restricted
+            };
+            (restrict(birth), restrict(survive))
+        };
+
+        Ok(Rule {
+            kind: RuleKind::Life {
+                birth,
+                survive,
+                neighborhood,
+            },
+            states,
+            topology: Topology::Torus,
+        })
+    }
+
+    // R5,C0,M1,S34..58,B34..45,NM
+    fn parse_ltl(&mut self) -> Result<Rule, RuleError> {
+        let mut range = None;
+        let mut states = 2;
+        let mut middle = false;
+        let mut survive = None;
+        let mut birth = None;
+        let mut shape = Shape::Moore;
+
+        while let Some((pos, c)) = self.bump() {
+            match c {
+                'R' => {
+                    let (pos, r) = self.parse_number("range")?;
+                    if !(1..=MAX_RANGE).contains(&r) {
+                        return Err(
+                            self.error(pos, format!("range must be between 1 and {}", MAX_RANGE))
+                        );
+                    }
+                    range = Some(r);
+                }
+                'C' => {
+                    let (pos, c) = self.parse_number("number of states")?;
+                    if c > MAX_STATES {
+                        return Err(self.error(
+                            pos,
+                            format!("number of states must be at most {}", MAX_STATES),
+                        ));
+                    }
+                    // C0 and C1 both mean a plain 2 state rule
+                    states = c.max(2);
+                }
+                'M' => {
+                    let (pos, m) = self.parse_number("0 or 1")?;
+                    if m > 1 {
+                        return Err(self.error(pos, "expected 0 or 1"));
+                    }
+                    middle = m == 1;
+                }
+                'S' => survive = Some(self.parse_count_range()?),
+                'B' => birth = Some(self.parse_count_range()?),
+                'N' => {
+                    shape = match self.bump() {
+                        Some((_, 'M')) => Shape::Moore,
+                        Some((_, 'N')) => Shape::VonNeumann,
+                        Some((_, 'C')) => Shape::Circular,
+                        Some((pos, c)) => {
+                            return Err(self.error(
+                                pos,
+                                format!("expected neighborhood 'M', 'N' or 'C', found '{}'", c),
+                            ));
+                        }
+                        None => return Err(self.error(self.end(), "expected neighborhood")),
+                    }
+                }
+                _ => {
+                    return Err(self.error(
+                        pos,
+                        format!(
+                            "expected one of 'R', 'C', 'M', 'S', 'B', 'N', found '{}'",
+                            c
+                        ),
+                    ));
+                }
+            }
+
+            match self.bump() {
+                Some((_, ',')) | None => {}
+                Some((pos, c)) => {
+                    return Err(self.error(pos, format!("expected ',', found '{}'", c)));
+                }
+            }
+        }
+
+        let (Some(range), Some(survive), Some(birth)) = (range, survive, birth) else {
+            return Err(self.error(
+                self.end(),
+                "Larger than Life rules need 'R', 'S' and 'B' sections",
+            ));
+        };
+
+        let ltl = Ltl {
+            range,
+            shape,
+            middle,
+            birth,
+            survive,
+        };
+
+        let max = ltl.max_count();
+        for (name, (_, hi)) in [("birth", birth), ("survival", survive)] {
+            if hi > max {
+                return Err(self.error(
+                    self.end(),
+                    format!(
+                        "{} count {} is more than the {} cells in the neighborhood",
+                        name, hi, max
+                    ),
+                ));
+            }
+        }
+
+        Ok(Rule {
+            kind: RuleKind::LargerThanLife(ltl),
+            states,
+            topology: Topology::Torus,
+        })
+    }
+
+    // 34..58, or a single count
+    fn parse_count_range(&mut self) -> Result<(u32, u32), RuleError> {
+        let (pos, lo) = self.parse_number("neighbor count")?;
+
+        let hi = if let Some((_, '.')) = self.peek() {
+            for _ in 0..2 {
+                match self.bump() {
+                    Some((_, '.')) => {}
+                    Some((pos, _)) => return Err(self.error(pos, "expected '..'")),
+                    None => return Err(self.error(self.end(), "expected '..'")),
+                }
+            }
+            self.parse_number("neighbor count")?.1
+        } else {
+            lo
+        };
+
+        if lo > hi {
+            return Err(self.error(pos, format!("empty range {}..{}", lo, hi)));
+        }
+
+        Ok((lo, hi))
+    }
+
+    // Returns the position the number started at along with it
+    fn parse_number(&mut self, what: &str) -> Result<(usize, u32), RuleError> {
+        let start = self.peek().map_or(self.end(), |(pos, _)| pos);
+        let mut number: u32 = 0;
+        let mut digits = 0;
+
+        while let Some((_, c)) = self.peek() {
+            let Some(d) = c.to_digit(10) else {
+                break;
+            };
+
+            number = number.saturating_mul(10).saturating_add(d);
+            digits += 1;
+            self.bump();
+        }
+
+        if digits == 0 {
+            Err(self.error(start, format!("expected {}", what)))
+        } else {
+            Ok((start, number))
+        }
+    }
+
+    fn parse_states(&mut self) -> Result<u32, RuleError> {
+        let (start, states) = self.parse_number("number of states")?;
+
+        if !(2..=MAX_STATES).contains(&states) {
+            Err(self.error(
+                start,
+                format!("number of states must be between 2 and {}", MAX_STATES),
+            ))
+        } else {
+            Ok(states)
+        }
+    }
+
+    // Digits, each optionally followed by Hensel letters or '-' and the
+    // letters to leave out
+    fn parse_counts(&mut self) -> Result<Transitions, RuleError> {
+        let mut table = Transitions::default();
+
+        while let Some((pos, c)) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+
+            let n = c.to_digit(10).unwrap() as usize;
+            if n > 8 {
+                return Err(self.error(pos, format!("{} is not a valid neighbor count", n)));
+            }
+            self.bump();
+
+            let letters = HENSEL[n];
+            let negate = matches!(self.peek(), Some((_, '-')));
+            if negate {
+                self.bump();
+            }
+
+            let mut chosen = Vec::new();
+            while let Some((letter_pos, c)) = self.peek() {
+                if !c.is_ascii_alphabetic() {
+                    break;
+                }
+
+                let letter = c.to_ascii_lowercase();
+                match letters.iter().find(|&&(l, _)| l == letter) {
+                    Some(&(_, config)) => chosen.push(config),
+                    // Start of the next section or the neighborhood suffix
+                    None if matches!(letter, 'b' | 's' | 'c' | 'g' | 'h' | 'v') => break,
+                    None => {
+                        return Err(self.error(
+                            letter_pos,
+                            format!("'{}' is not a valid letter for {} neighbors", letter, n),
+                        ));
+                    }
+                }
+                self.bump();
+            }
+
+            if negate && chosen.is_empty() {
+                return Err(self.error(pos, format!("expected letters after '{}-'", n)));
+            }
+            self.counts.push((pos, n as u32, !chosen.is_empty()));
+
+            if chosen.is_empty() || negate {
+                for config in 0..=255u8 {
+                    if config.count_ones() as usize == n {
+                        table.set(config, true);
+                    }
+                }
+            }
+            for config in chosen {
+                for image in symmetries(config) {
+                    table.set(image, !negate);
+                }
+            }
+        }
+
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(rule: &str) -> Rule {
+        rule.parse()
+            .unwrap_or_else(|e| panic!("{} should parse: {}", rule, e))
+    }
+
+    #[test]
+    fn notations_of_life_agree() {
+        let life = parse("B3/S23");
+        for rule in [
+            "23/3",
+            "b3s23",
+            "S23/B3",
+            "B3/S23:T",
+            "B3/S23:T0,0",
+            " B3 / S23 ",
+        ] {
+            assert_eq!(parse(rule), life, "{}", rule);
+        }
+        assert_eq!(life.states, 2);
+        assert_eq!(life.topology, Topology::Torus);
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for rule in [
+            "B3/S23",
+            "B36/S23",
+            "B0/S8",
+            "B2/S/C3",
+            "B2/S34H",
+            "B1/S1V",
+            "B2a/S23",
+            "B3-k/S23:K",
+            "B2kan3/S4-z:C",
+            "B3/S23:P",
+            "R5,C0,M1,S34..58,B34..45,NM",
+            "R3,C4,M0,S2..5,B3..4,NC:K",
+        ] {
+            let parsed = parse(rule);
+            assert_eq!(parsed.to_string(), rule);
+            assert_eq!(parse(&parsed.to_string()), parsed, "{}", rule);
+        }
+
+        assert_eq!(parse("23/3").to_string(), "B3/S23");
+        assert_eq!(parse("345/2/4").to_string(), "B2/S345/C4");
+        assert_eq!(parse("b2i3s").to_string(), "B2i3/S");
+        assert_eq!(parse("B2-cei3/S4-z:C").to_string(), "B2kan3/S4-z:C");
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        for (rule, position, message) in [
+            ("", 0, "rule is empty"),
+            ("B9/S23", 1, "not a valid neighbor count"),
+            ("B3/S2x", 5, "'x' is not a valid letter for 2 neighbors"),
+            ("B3/S23/", 6, "trailing '/'"),
+            ("B3 / S2é", 7, "found 'é'"),
+            ("B3a/S23V", 1, "Hensel letters only work"),
+            ("B3/S23/C300", 8, "number of states"),
+            ("B3/S23:Q", 7, "expected topology"),
+            ("B3/S23:T10,10", 8, "board sizes are not supported"),
+            ("B3/S23:T0,0,0", 8, "expected a board size"),
+            ("23", 2, "expected '/' between S and B"),
+            ("R5,C0,M1,S34..58,B34..45,NQ", 26, "expected neighborhood"),
+            ("R5,S34..58", 10, "need 'R', 'S' and 'B'"),
+        ] {
+            let error = rule.parse::<Rule>().unwrap_err();
+            assert_eq!(error.position, position, "{}: {}", rule, error);
+            assert!(error.message.contains(message), "{}: {}", rule, error);
+        }
+    }
+
+    fn orbit(config: u8) -> Vec<u8> {
+        let mut orbit = symmetries(config).collect::<Vec<u8>>();
+        orbit.sort();
+        orbit.dedup();
+        orbit
+    }
+
+    #[test]
+    fn hensel_letters_split_every_count() {
+        for (n, letters) in HENSEL.iter().enumerate() {
+            let mut configs = letters
+                .iter()
+                .flat_map(|&(letter, config)| {
+                    assert_eq!(config.count_ones() as usize, n, "{}{}", n, letter);
+                    orbit(config)
+                })
+                .collect::<Vec<u8>>();
+            if letters.is_empty() {
+                configs.push((0xFFu16 >> (8 - n)) as u8);
+            }
+
+            // Every configuration with n neighbors, each under one letter
+            let all = (0..=255u8)
+                .filter(|config| config.count_ones() as usize == n)
+                .collect::<Vec<u8>>();
+            configs.sort();
+            assert_eq!(configs, all, "{} neighbors", n);
+        }
+    }
+
+    #[test]
+    fn hensel_orbit_sizes() {
+        let sizes = HENSEL.map(|letters| {
+            letters
+                .iter()
+                .map(|&(letter, config)| format!("{}{}", letter, orbit(config).len()))
+                .collect::<Vec<String>>()
+                .join(" ")
+        });
+
+        assert_eq!(
+            sizes,
+            [
+                "",
+                "c4 e4",
+                "c4 e4 k8 a8 i2 n2",
+                "c4 e4 k4 a4 i4 n8 y4 q8 j8 r8",
+                "c1 e1 k8 a8 i4 n8 y8 q4 j8 r8 t4 w4 z4",
+                "c4 e4 k4 a4 i4 n8 y4 q8 j8 r8",
+                "c4 e4 k8 a8 i2 n2",
+                "c4 e4",
+                "",
+            ]
+        );
+    }
+
+    // The dead neighbors of a configuration make one of 8 - n alive ones,
+    // with the same letter except among the 4s
+    #[test]
+    fn hensel_complements() {
+        let four = [
+            ('c', 'e'),
+            ('k', 'k'),
+            ('a', 'a'),
+            ('i', 't'),
+            ('n', 'r'),
+            ('y', 'j'),
+            ('z', 'z'),
+            ('q', 'w'),
+        ];
+
+        for (n, letters) in HENSEL.iter().enumerate() {
+            for &(letter, config) in *letters {
+                let complement = orbit(!config);
+                let (found, _) = HENSEL[8 - n]
+                    .iter()
+                    .find(|&&(_, other)| complement.contains(&other))
+                    .unwrap();
+
+                let expected = if n == 4 {
+                    four.iter()
+                        .find_map(|&(a, b)| {
+                            (letter == a).then_some(b).or((letter == b).then_some(a))
+                        })
+                        .unwrap()
+                } else {
+                    letter
+                };
+                assert_eq!(*found, expected, "{}{}", n, letter);
+            }
+        }
+    }
+
+    #[test]
+    fn life_in_letters_is_life() {
+        let letters = parse("B3cekainyqjr/S2cekain3cekainyqjr");
+        assert_eq!(letters, parse("B3/S23"));
+        assert_eq!(letters.to_string(), "B3/S23");
+
+        // And a letter short isn't
+        let short = parse("B3cekainyqj/S2cekain3cekainyqjr");
+        assert_ne!(short, parse("B3/S23"));
+        assert_eq!(short.to_string(), "B3-r/S23");
+    }
+}
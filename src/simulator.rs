@@ -0,0 +1,198 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::game_of_life::{BasicGoL, PackedGoL};
+use crate::hashlife::HashLifeBoard;
+use crate::rule::Rule;
+
+// A rectangle of cells, with rows going down from y
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // Positions of the cells in the region, rows first
+    pub fn cells(self) -> impl Iterator<Item = (usize, usize)> {
+        (self.y..self.y + self.height)
+            .flat_map(move |y| (self.x..self.x + self.width).map(move |x| (x, y)))
+    }
+}
+
+// What the wallpaper and the tools need from a simulation, whether it runs in
+// a shader or on the CPU. Cells hold states of the rule, 0 being dead and 1
+// alive, and boards are flat grids with rows going down
+pub trait Simulator {
+    fn size(&self) -> (usize, usize);
+
+    // Switches rules without touching the board
+    fn set_rule(&mut self, rule: &Rule) -> Result<(), &'static str>;
+
+    fn tick(&mut self, generations: u64);
+
+    // Overwrites the cells of a region, which has to be on the board, with
+    // data given rows first
+    fn set_cells(&mut self, region: Region, data: &[u8]);
+
+    fn read_state(&self) -> Vec<u8>;
+
+    // Waits for the generations tick started, for engines that return before
+    // they're done
+    fn finish(&self) {}
+
+    // Ticks from the current board and returns how many milliseconds the
+    // generations took
+    fn bench(&mut self, generations: u64) -> f64 {
+        let start = Instant::now();
+        self.tick(generations);
+        self.finish();
+        start.elapsed().as_secs_f64() * 1000.0
+    }
+
+    // Prints the board with one string for dead cells and another for the
+    // rest, rows going down
+    fn print(&self, dead: &str, alive: &str) {
+        let (width, height) = self.size();
+        let mut output = String::with_capacity((dead.len() * width + 1) * height);
+        print_blocks(
+            &mut output,
+            &self.read_state(),
+            width,
+            (1, 1),
+            (width, height),
+            |output, cells| output.push_str(if cells[0] == 0 { dead } else { alive }),
+        );
+
+        print!("{}", output);
+    }
+
+    // Number of alive cells, dying ones don't count
+    fn population(&self) -> u64 {
+        self.read_state().iter().filter(|&&cell| cell == 1).count() as u64
+    }
+
+    fn clear(&mut self) {
+        let (width, height) = self.size();
+        self.set_cells(Region::new(0, 0, width, height), &vec![0; width * height]);
+    }
+
+    // Makes each cell alive with the given probability, the same seed gives
+    // the same board on every engine
+    fn randomize(&mut self, density: f64, seed: u64) {
+        let (width, height) = self.size();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let data = (0..width * height)
+            .map(|_| rng.gen_bool(density) as u8)
+            .collect::<Vec<u8>>();
+
+        self.set_cells(Region::new(0, 0, width, height), &data);
+    }
+}
+
+// Writes a board as text, one character for every block of cells and columns
+// by rows of characters, each row ending in a newline. glyph writes a block
+// from its cells, rows going down, the cells past the board being dead
+pub fn print_blocks(
+    output: &mut String,
+    state: &[u8],
+    width: usize,
+    (block_width, block_height): (usize, usize),
+    (columns, rows): (usize, usize),
+    mut glyph: impl FnMut(&mut String, &[u8]),
+) {
+    let height = state.len().checked_div(width).unwrap_or(0);
+    let mut cells = vec![0; block_width * block_height];
+
+    for row in 0..rows {
+        for column in 0..columns {
+            for (i, cell) in cells.iter_mut().enumerate() {
+                let x = column * block_width + i % block_width;
+                let y = row * block_height + i / block_width;
+                *cell = if x < width && y < height {
+                    state[y * width + x]
+                } else {
+                    0
+                };
+            }
+            glyph(output, &cells);
+        }
+        output.push('\n');
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Gpu,
+    Basic,
+    Packed,
+    Threaded,
+    HashLife,
+}
+
+impl Engine {
+    // One of the CPU engines running the rule on an empty board
+    pub fn new_cpu(
+        self,
+        width: usize,
+        height: usize,
+        rule: &Rule,
+    ) -> Result<Box<dyn Simulator>, &'static str> {
+        Ok(match self {
+            Engine::Gpu => return Err("The GPU engine needs a GL context"),
+            Engine::Basic => Box::new(BasicGoL::new(width, height, rule)),
+            Engine::Packed => Box::new(PackedGoL::new(width, height, rule)?),
+            Engine::Threaded => {
+                let mut basic = BasicGoL::new(width, height, rule);
+                basic.set_threads(std::thread::available_parallelism().map_or(1, |n| n.get()));
+                Box::new(basic)
+            }
+            Engine::HashLife => Box::new(HashLifeBoard::new(width, height, rule)?),
+        })
+    }
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "gpu" => Ok(Engine::Gpu),
+            "basic" => Ok(Engine::Basic),
+            "packed" => Ok(Engine::Packed),
+            "threaded" => Ok(Engine::Threaded),
+            "hashlife" => Ok(Engine::HashLife),
+            _ => Err(format!(
+                "unknown engine '{}', expected gpu, basic, packed, threaded or hashlife",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Engine::Gpu => "gpu",
+            Engine::Basic => "basic",
+            Engine::Packed => "packed",
+            Engine::Threaded => "threaded",
+            Engine::HashLife => "hashlife",
+        })
+    }
+}
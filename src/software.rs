@@ -0,0 +1,610 @@
+use std::ptr::null_mut;
+use std::time::{Duration, Instant};
+
+use glfw::{Modifiers, MouseButton};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::shm::{self, ConnectionExt as ShmConnectionExt};
+use x11rb::protocol::xproto::{
+    ConnectionExt, CreateGCAux, CreateWindowAux, EventMask, Gcontext, ImageFormat, ImageOrder,
+    KeyButMask, Keycode, Screen, Window, WindowClass,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as WrapperConnectionExt;
+
+use crate::rule::Rule;
+use crate::simulator::{Engine, Simulator};
+use crate::{
+    click, fast_forward, hex_cell, make_window_wallpaper, save_board, Brush, Color, Colors, Saving,
+    Start,
+};
+
+const XK_TAB: u32 = 0xff09;
+const XK_ESCAPE: u32 = 0xff1b;
+const XK_F: u32 = 0x66;
+const XK_S: u32 = 0x73;
+const XK_O: u32 = 0x6f;
+const XK_BRACKETLEFT: u32 = 0x5b;
+const XK_BRACKETRIGHT: u32 = 0x5d;
+
+// Draws the wallpaper without OpenGL, for machines and VMs without a 3.3 core
+// context. A CPU engine runs the simulation and every frame is sent to the X
+// server with put_image, through shared memory when the server has MIT-SHM
+pub struct SoftwareWallpaper {
+    conn: RustConnection,
+    window: Window,
+    gc: Gcontext,
+    width: u16,
+    height: u16,
+    depth: u8,
+    format: PixelFormat,
+    // Not available over the network
+    shm: Option<SharedImage>,
+
+    engine: Box<dyn Simulator>,
+    delay: f64,
+    // Index of the board cell every pixel of the window shows, rows going
+    // down, or None past the edge of a plane
+    pixel_cells: Vec<Option<u32>>,
+    // Pixel value of every state
+    palette: Vec<u32>,
+
+    rule: Rule,
+    // Rules to cycle through with Tab, and which one of them is running
+    rules: Vec<Rule>,
+    rule_index: usize,
+    colors: Colors,
+    rng: StdRng,
+    // What middle click puts down
+    brush: Brush,
+    // Where S saves the board
+    save: Saving,
+
+    // Keysyms of every keycode, starting at min_keycode
+    min_keycode: Keycode,
+    keysyms_per_keycode: usize,
+    keysyms: Vec<u32>,
+}
+
+impl SoftwareWallpaper {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        scale: u32,
+        period: f64,
+        rules: Vec<Rule>,
+        colors: Colors,
+        engine: Engine,
+        seed: u64,
+        start: Start,
+        save: Saving,
+    ) -> Result<Self, String> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let screen = conn.setup().roots[screen_num].clone();
+        let (width, height) = (screen.width_in_pixels, screen.height_in_pixels);
+
+        let format = PixelFormat::new(&conn, &screen)?;
+
+        // The GPU engine needs OpenGL, which is what is missing
+        let engine = match engine {
+            Engine::Gpu => Engine::Threaded,
+            engine => engine,
+        };
+        let (board_width, board_height) = (width as u32 / scale, height as u32 / scale);
+        let mut engine = engine.new_cpu(board_width as usize, board_height as usize, &rules[0])?;
+        start.apply(engine.as_mut());
+
+        let window = conn.generate_id().map_err(|e| e.to_string())?;
+        conn.create_window(
+            screen.root_depth,
+            window,
+            screen.root,
+            0,
+            0,
+            width,
+            height,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            &CreateWindowAux::new()
+                .background_pixel(screen.black_pixel)
+                .event_mask(EventMask::EXPOSURE | EventMask::KEY_PRESS | EventMask::BUTTON_PRESS),
+        )
+        .unwrap()
+        .check()
+        .map_err(|e| e.to_string())?;
+
+        let gc = conn.generate_id().map_err(|e| e.to_string())?;
+        conn.create_gc(gc, window, &CreateGCAux::new()).unwrap();
+
+        make_window_wallpaper(&conn, window, width as u32, height as u32);
+
+        let setup = conn.setup();
+        let mapping = conn
+            .get_keyboard_mapping(setup.min_keycode, setup.max_keycode - setup.min_keycode + 1)
+            .unwrap()
+            .reply()
+            .map_err(|e| e.to_string())?;
+
+        let shm = SharedImage::new(&conn, format.row_bytes(width as usize) * height as usize);
+        if shm.is_none() {
+            println!("MIT-SHM is not available, sending every frame over the connection");
+        }
+
+        let mut software = SoftwareWallpaper {
+            window,
+            gc,
+            width,
+            height,
+            depth: screen.root_depth,
+            format,
+            shm,
+
+            engine,
+            delay: period,
+            pixel_cells: Vec::new(),
+            palette: Vec::new(),
+
+            rule: rules[0],
+            rules,
+            rule_index: 0,
+            colors,
+            rng: StdRng::seed_from_u64(seed),
+            brush: Brush::new(start.pattern),
+            save,
+
+            min_keycode: setup.min_keycode,
+            keysyms_per_keycode: mapping.keysyms_per_keycode as usize,
+            keysyms: mapping.keysyms,
+
+            conn,
+        };
+
+        let rule = software.rule;
+        software.set_rule(&rule)?;
+        Ok(software)
+    }
+
+    fn set_rule(&mut self, rule: &Rule) -> Result<(), &'static str> {
+        self.engine.set_rule(rule)?;
+
+        self.palette = self
+            .colors
+            .palette(rule.states)
+            .into_iter()
+            .map(|color| self.format.pixel(color))
+            .collect();
+        self.pixel_cells = pixel_cells(
+            (self.width as usize, self.height as usize),
+            self.engine.size(),
+            rule,
+        );
+
+        self.rule = *rule;
+        Ok(())
+    }
+
+    pub fn main_loop(&mut self) {
+        let mut last_tick = Instant::now() - Duration::from_secs(1);
+        let max_delay_time = Duration::from_secs_f64(self.delay);
+        let mut should_close = false;
+
+        while !should_close {
+            let mut should_redraw = false;
+
+            while let Some(event) = self.conn.poll_for_event().unwrap() {
+                match event {
+                    Event::Expose(_) => {
+                        should_redraw = true;
+                    }
+                    Event::KeyPress(key) => match self.keysym(key.detail) {
+                        XK_ESCAPE => {
+                            should_close = true;
+                        }
+                        XK_TAB => {
+                            // On to the next rule the engine can run
+                            for offset in 1..=self.rules.len() {
+                                let index = (self.rule_index + offset) % self.rules.len();
+                                let rule = self.rules[index];
+                                match self.set_rule(&rule) {
+                                    Ok(()) => {
+                                        self.rule_index = index;
+                                        println!("Rule: {}", rule);
+                                        break;
+                                    }
+                                    Err(e) => println!("{}: {}", rule, e),
+                                }
+                            }
+                            should_redraw = true;
+                        }
+                        XK_F => {
+                            println!("{}", fast_forward(self.engine.as_mut(), &self.rule, 1000));
+                            should_redraw = true;
+                        }
+                        XK_BRACKETLEFT => {
+                            println!("Stamping {}", self.brush.cycle(-1));
+                        }
+                        XK_BRACKETRIGHT => {
+                            println!("Stamping {}", self.brush.cycle(1));
+                        }
+                        XK_O => {
+                            let shift = key.state & u16::from(KeyButMask::SHIFT) != 0;
+                            let step = if shift { -1 } else { 1 };
+                            println!("Stamping turned {}", self.brush.turn(step));
+                        }
+                        XK_S => match save_board(self.engine.as_ref(), &self.rule, &self.save) {
+                            Ok(path) => println!("Saved the board to {}", path.display()),
+                            Err(e) => println!("{}", e),
+                        },
+                        _ => {}
+                    },
+                    Event::ButtonPress(press) => {
+                        // X numbers the middle button 2 and the right one 3
+                        let button = match press.detail {
+                            1 => MouseButton::Button1,
+                            3 => MouseButton::Button2,
+                            2 => MouseButton::Button3,
+                            _ => continue,
+                        };
+
+                        let mut mods = Modifiers::empty();
+                        if press.state & u16::from(KeyButMask::CONTROL) != 0 {
+                            mods |= Modifiers::Control;
+                        }
+                        if press.state & u16::from(KeyButMask::SHIFT) != 0 {
+                            mods |= Modifiers::Shift;
+                        }
+
+                        let Some(cell) = self.cell_at(press.event_x, press.event_y) else {
+                            continue;
+                        };
+
+                        click(
+                            self.engine.as_mut(),
+                            cell,
+                            button,
+                            mods,
+                            &mut self.rng,
+                            &self.brush.pattern,
+                        );
+                        should_redraw = true;
+                    }
+                    _ => {}
+                }
+            }
+
+            let now = Instant::now();
+            let delta = now.duration_since(last_tick);
+            let tick = delta >= max_delay_time;
+
+            if should_redraw || tick {
+                if tick {
+                    last_tick = now;
+                    self.engine.tick(1);
+                }
+                self.draw();
+            } else {
+                // There is no waiting for events with a timeout
+                std::thread::sleep((max_delay_time - delta).min(Duration::from_millis(10)));
+            }
+        }
+    }
+
+    fn keysym(&self, keycode: Keycode) -> u32 {
+        let i = (keycode - self.min_keycode) as usize * self.keysyms_per_keycode;
+        self.keysyms.get(i).copied().unwrap_or(0)
+    }
+
+    // Cell under a point on the window, as drawn, with rows going down
+    fn cell_at(&self, x: i16, y: i16) -> Option<(usize, usize)> {
+        if x < 0 || y < 0 || x as u16 >= self.width || y as u16 >= self.height {
+            return None;
+        }
+
+        let cell = self.pixel_cells[y as usize * self.width as usize + x as usize]? as usize;
+        let (board_width, _) = self.engine.size();
+        Some((cell % board_width, cell / board_width))
+    }
+
+    fn draw(&mut self) {
+        let state = self.engine.read_state();
+        let dead = self.palette[0];
+        let pixels = self.pixel_cells.iter().map(|cell| match cell {
+            // States left over from the last rule are drawn dead until they die
+            Some(cell) => *self
+                .palette
+                .get(state[*cell as usize] as usize)
+                .unwrap_or(&dead),
+            None => dead,
+        });
+
+        match &mut self.shm {
+            Some(shm) => {
+                self.format.write(self.width as usize, pixels, shm.bytes());
+                self.conn
+                    .shm_put_image(
+                        self.window,
+                        self.gc,
+                        self.width,
+                        self.height,
+                        0,
+                        0,
+                        self.width,
+                        self.height,
+                        0,
+                        0,
+                        self.depth,
+                        ImageFormat::Z_PIXMAP.into(),
+                        false,
+                        shm.seg,
+                        0,
+                    )
+                    .unwrap();
+            }
+            None => {
+                let row_bytes = self.format.row_bytes(self.width as usize);
+                let mut bytes = vec![0; row_bytes * self.height as usize];
+                self.format.write(self.width as usize, pixels, &mut bytes);
+
+                // Big frames don't fit in one request
+                let rows = (self.conn.maximum_request_bytes() - 32) / row_bytes;
+                for (i, band) in bytes.chunks(rows * row_bytes).enumerate() {
+                    self.conn
+                        .put_image(
+                            ImageFormat::Z_PIXMAP,
+                            self.window,
+                            self.gc,
+                            self.width,
+                            (band.len() / row_bytes) as u16,
+                            0,
+                            (i * rows) as i16,
+                            0,
+                            self.depth,
+                            band,
+                        )
+                        .unwrap();
+                }
+            }
+        }
+
+        // Also makes sure the server is done reading the shared image before
+        // the next frame overwrites it
+        self.conn.sync().unwrap();
+    }
+}
+
+impl Drop for SoftwareWallpaper {
+    fn drop(&mut self) {
+        if let Some(shm) = &self.shm {
+            let _ = self.conn.shm_detach(shm.seg);
+            let _ = self.conn.sync();
+        }
+    }
+}
+
+// Which board cell every pixel of a window shows, the same way the copy
+// shader stretches the board over the window
+pub fn pixel_cells(
+    (width, height): (usize, usize),
+    (board_width, board_height): (usize, usize),
+    rule: &Rule,
+) -> Vec<Option<u32>> {
+    let (board_width, board_height) = (board_width as i64, board_height as i64);
+
+    (0..height)
+        .flat_map(|py| (0..width).map(move |px| (px, py)))
+        .map(|(px, py)| {
+            // The shader works from the bottom of the window, at pixel centers
+            let x = (px as f64 + 0.5) / width as f64 * board_width as f64;
+            let y = ((height - 1 - py) as f64 + 0.5) / height as f64 * board_height as f64;
+
+            let (x, y) = if rule.is_hexagonal() {
+                hex_cell(x, y)
+            } else {
+                (x.floor() as i64, y.floor() as i64)
+            };
+
+            let (x, y) = rule.topology.wrap(x, y, board_width, board_height)?;
+            Some(((board_height - 1 - y) * board_width + x) as u32)
+        })
+        .collect()
+}
+
+// How colors turn into pixel values of the root visual, and how those are
+// laid out in an image
+struct PixelFormat {
+    // Shift and width of the red, green and blue bits
+    channels: [(u32, u32); 3],
+    // 2, 3 or 4
+    bytes_per_pixel: usize,
+    // Rows are padded to a multiple of this many bytes
+    row_pad: usize,
+    msb_first: bool,
+}
+
+impl PixelFormat {
+    fn new(conn: &RustConnection, screen: &Screen) -> Result<Self, String> {
+        let setup = conn.setup();
+
+        let pixmap_format = setup
+            .pixmap_formats
+            .iter()
+            .find(|format| format.depth == screen.root_depth)
+            .ok_or("Couldn't find the pixel format of the screen")?;
+        if ![16, 24, 32].contains(&pixmap_format.bits_per_pixel) {
+            return Err(format!(
+                "Only 16, 24 and 32 bit pixels are supported, the screen has {}",
+                pixmap_format.bits_per_pixel
+            ));
+        }
+
+        let visual = screen
+            .allowed_depths
+            .iter()
+            .flat_map(|depth| &depth.visuals)
+            .find(|visual| visual.visual_id == screen.root_visual)
+            .ok_or("Couldn't find the root visual")?;
+
+        let channel = |mask: u32| (mask.trailing_zeros(), mask.count_ones());
+
+        Ok(PixelFormat {
+            channels: [
+                channel(visual.red_mask),
+                channel(visual.green_mask),
+                channel(visual.blue_mask),
+            ],
+            bytes_per_pixel: pixmap_format.bits_per_pixel as usize / 8,
+            row_pad: pixmap_format.scanline_pad as usize / 8,
+            msb_first: setup.image_byte_order == ImageOrder::MSB_FIRST,
+        })
+    }
+
+    fn row_bytes(&self, width: usize) -> usize {
+        (width * self.bytes_per_pixel).next_multiple_of(self.row_pad)
+    }
+
+    fn pixel(&self, color: Color) -> u32 {
+        [color.0, color.1, color.2]
+            .iter()
+            .zip(self.channels)
+            .fold(0, |pixel, (&value, (shift, bits))| {
+                pixel | (value as u32 >> (8 - bits.min(8))) << shift
+            })
+    }
+
+    // Rows of width pixels, the padding at their ends left as it is
+    fn write(&self, width: usize, mut pixels: impl Iterator<Item = u32>, bytes: &mut [u8]) {
+        let size = self.bytes_per_pixel;
+
+        for row in bytes.chunks_exact_mut(self.row_bytes(width)) {
+            let row = row.chunks_exact_mut(size);
+            for (pixel, out) in pixels.by_ref().take(width).zip(row) {
+                if self.msb_first {
+                    out.copy_from_slice(&pixel.to_be_bytes()[4 - size..]);
+                } else {
+                    out.copy_from_slice(&pixel.to_le_bytes()[..size]);
+                }
+            }
+        }
+    }
+}
+
+// Memory shared with the X server, that frames are written to
+struct SharedImage {
+    seg: shm::Seg,
+    addr: *mut u8,
+    len: usize,
+}
+
+impl SharedImage {
+    fn new(conn: &RustConnection, len: usize) -> Option<Self> {
+        conn.extension_information(shm::X11_EXTENSION_NAME).ok()??;
+        conn.shm_query_version().ok()?.reply().ok()?;
+        let seg = conn.generate_id().ok()?;
+
+        unsafe {
+            let id = libc::shmget(libc::IPC_PRIVATE, len, libc::IPC_CREAT | 0o600);
+            if id < 0 {
+                return None;
+            }
+
+            let addr = libc::shmat(id, null_mut(), 0);
+            let attached = addr as isize != -1
+                && conn
+                    .shm_attach(seg, id as u32, false)
+                    .ok()
+                    .and_then(|cookie| cookie.check().ok())
+                    .is_some();
+
+            // The segment is freed once both sides detach from it
+            libc::shmctl(id, libc::IPC_RMID, null_mut());
+
+            if !attached {
+                if addr as isize != -1 {
+                    libc::shmdt(addr);
+                }
+                return None;
+            }
+
+            Some(SharedImage {
+                seg,
+                addr: addr as *mut u8,
+                len,
+            })
+        }
+    }
+
+    fn bytes(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.addr, self.len) }
+    }
+}
+
+impl Drop for SharedImage {
+    fn drop(&mut self) {
+        unsafe {
+            libc::shmdt(self.addr as *const libc::c_void);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(masks: [u32; 3], bits_per_pixel: usize, msb_first: bool) -> PixelFormat {
+        PixelFormat {
+            channels: masks.map(|mask| (mask.trailing_zeros(), mask.count_ones())),
+            bytes_per_pixel: bits_per_pixel / 8,
+            row_pad: 4,
+            msb_first,
+        }
+    }
+
+    #[test]
+    fn rgb565() {
+        let format = format([0xf800, 0x07e0, 0x001f], 16, false);
+        let pixel = format.pixel(Color(0xff, 0x80, 0x08, 0xff));
+        assert_eq!(pixel, 0xfc01);
+
+        // Three pixels take 6 bytes, padded to 8
+        let mut bytes = [0xaa; 16];
+        format.write(
+            3,
+            [pixel, 0, 0x1234].into_iter().cycle().take(6),
+            &mut bytes,
+        );
+        assert_eq!(
+            bytes,
+            [1, 0xfc, 0, 0, 0x34, 0x12, 0xaa, 0xaa, 1, 0xfc, 0, 0, 0x34, 0x12, 0xaa, 0xaa]
+        );
+    }
+
+    #[test]
+    fn packed_24_bits() {
+        let format = format([0xff0000, 0x00ff00, 0x0000ff], 24, true);
+        let pixel = format.pixel(Color(1, 2, 3, 0xff));
+        assert_eq!(pixel, 0x010203);
+
+        let mut bytes = [0; 16];
+        format.write(
+            2,
+            [pixel, 0x0a0b0c, 0x0d0e0f, pixel].into_iter(),
+            &mut bytes,
+        );
+        assert_eq!(
+            bytes,
+            [1, 2, 3, 0xa, 0xb, 0xc, 0, 0, 0xd, 0xe, 0xf, 1, 2, 3, 0, 0]
+        );
+    }
+
+    #[test]
+    fn unpadded_32_bits() {
+        let format = format([0xff0000, 0x00ff00, 0x0000ff], 32, false);
+        assert_eq!(format.row_bytes(3), 12);
+
+        let mut bytes = [0; 8];
+        format.write(1, [0x00102030, 0x00405060].into_iter(), &mut bytes);
+        assert_eq!(bytes, [0x30, 0x20, 0x10, 0, 0x60, 0x50, 0x40, 0]);
+    }
+}
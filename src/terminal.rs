@@ -0,0 +1,626 @@
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use glfw::{Modifiers, MouseButton};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::rule::Rule;
+use crate::simulator::{print_blocks, Engine, Region, Simulator};
+use crate::{click, fast_forward, save_board, Brush, Color, Colors, Saving, Start};
+
+// How cells are packed into characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyphs {
+    // ▀ with the top cell in the foreground and the bottom one in the
+    // background, 1x2 cells per character
+    Half,
+    // Dots for the cells that aren't dead, 2x4 cells per character in a
+    // single color
+    Braille,
+}
+
+impl Glyphs {
+    // Cells per character
+    fn cell_size(self) -> (usize, usize) {
+        match self {
+            Glyphs::Half => (1, 2),
+            Glyphs::Braille => (2, 4),
+        }
+    }
+}
+
+impl FromStr for Glyphs {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "half" => Ok(Glyphs::Half),
+            "braille" => Ok(Glyphs::Braille),
+            _ => Err(format!(
+                "unknown glyphs '{}', expected half or braille",
+                value
+            )),
+        }
+    }
+}
+
+// Braille dot of every cell in a 2x4 block, rows going down
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+const HELP: &str =
+    "Tab rule, f fast forward, space pause, r random, c clear, [ ] stamp, o turn, s save, q quit";
+
+// Runs the simulation in a terminal with 24 bit colors, for when there is no
+// X server at all. The board fills the terminal, less a status line, and
+// follows it when it is resized. Hexagonal rules are drawn unskewed
+pub struct TerminalWallpaper {
+    terminal: RawTerminal,
+    glyphs: Glyphs,
+    // Terminal size in characters
+    columns: usize,
+    rows: usize,
+
+    kind: Engine,
+    engine: Box<dyn Simulator>,
+    delay: f64,
+    paused: bool,
+
+    rule: Rule,
+    // Rules to cycle through with Tab, and which one of them is running
+    rules: Vec<Rule>,
+    rule_index: usize,
+    colors: Colors,
+    palette: Vec<Color>,
+    rng: StdRng,
+    // What middle click puts down
+    brush: Brush,
+    // Where s saves the board
+    save: Saving,
+    // Shown in the status line, printing would scroll the board
+    message: String,
+}
+
+impl TerminalWallpaper {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        period: f64,
+        rules: Vec<Rule>,
+        colors: Colors,
+        engine: Engine,
+        seed: u64,
+        start: Start,
+        glyphs: Glyphs,
+        save: Saving,
+    ) -> Result<Self, String> {
+        let terminal = RawTerminal::new()?;
+        let (columns, rows) = terminal_size().ok_or("Couldn't get the terminal size")?;
+
+        // There is no GL context to run the GPU engine in
+        let kind = match engine {
+            Engine::Gpu => Engine::Threaded,
+            engine => engine,
+        };
+        let (width, height) = board_size(columns, rows, glyphs);
+        let mut engine = kind.new_cpu(width, height, &rules[0])?;
+        start.apply(engine.as_mut());
+
+        let mut wallpaper = TerminalWallpaper {
+            terminal,
+            glyphs,
+            columns,
+            rows,
+
+            kind,
+            engine,
+            delay: period,
+            paused: false,
+
+            rule: rules[0],
+            rules,
+            rule_index: 0,
+            colors,
+            palette: Vec::new(),
+            rng: StdRng::seed_from_u64(seed),
+            brush: Brush::new(start.pattern),
+            save,
+            message: String::new(),
+        };
+
+        let rule = wallpaper.rule;
+        wallpaper.set_rule(&rule)?;
+        Ok(wallpaper)
+    }
+
+    fn set_rule(&mut self, rule: &Rule) -> Result<(), &'static str> {
+        self.engine.set_rule(rule)?;
+        self.palette = self.colors.palette(rule.states);
+        self.rule = *rule;
+        Ok(())
+    }
+
+    pub fn main_loop(&mut self) {
+        let mut last_tick = Instant::now() - Duration::from_secs(1);
+        let max_delay_time = Duration::from_secs_f64(self.delay);
+        let mut should_redraw = true;
+
+        loop {
+            if let Some((columns, rows)) = terminal_size() {
+                if (columns, rows) != (self.columns, self.rows) {
+                    self.resize(columns, rows);
+                    should_redraw = true;
+                }
+            }
+
+            let now = Instant::now();
+            let delta = now.duration_since(last_tick);
+            if delta >= max_delay_time {
+                last_tick = now;
+                if !self.paused {
+                    self.engine.tick(1);
+                    should_redraw = true;
+                }
+            }
+
+            if should_redraw {
+                self.draw();
+                should_redraw = false;
+            }
+
+            // Resizes only show up on the next pass, so don't sleep for long
+            let timeout = max_delay_time
+                .saturating_sub(last_tick.elapsed())
+                .min(Duration::from_millis(50));
+
+            for input in self.terminal.read(timeout) {
+                match input {
+                    Input::Quit => return,
+                    Input::Key(b'\t') => {
+                        // On to the next rule the engine can run
+                        for offset in 1..=self.rules.len() {
+                            let index = (self.rule_index + offset) % self.rules.len();
+                            let rule = self.rules[index];
+                            match self.set_rule(&rule) {
+                                Ok(()) => {
+                                    self.rule_index = index;
+                                    break;
+                                }
+                                Err(e) => self.message = format!("{}: {}", rule, e),
+                            }
+                        }
+                    }
+                    Input::Key(b'f') => {
+                        self.message = fast_forward(self.engine.as_mut(), &self.rule, 1000);
+                    }
+                    Input::Key(b' ') => {
+                        self.paused = !self.paused;
+                    }
+                    Input::Key(b'r') => {
+                        self.engine.randomize(0.5, self.rng.gen());
+                    }
+                    Input::Key(b'c') => {
+                        self.engine.clear();
+                    }
+                    Input::Key(b'[') => {
+                        self.message = format!("stamping {}", self.brush.cycle(-1));
+                    }
+                    Input::Key(b']') => {
+                        self.message = format!("stamping {}", self.brush.cycle(1));
+                    }
+                    Input::Key(b'o') => {
+                        self.message = format!("stamping turned {}", self.brush.turn(1));
+                    }
+                    Input::Key(b'O') => {
+                        self.message = format!("stamping turned {}", self.brush.turn(-1));
+                    }
+                    Input::Key(b's') => {
+                        self.message =
+                            match save_board(self.engine.as_ref(), &self.rule, &self.save) {
+                                Ok(path) => format!("saved {}", path.display()),
+                                Err(e) => e,
+                            };
+                    }
+                    Input::Click(column, row, button, mods) => {
+                        let (cell_width, cell_height) = self.glyphs.cell_size();
+                        let (width, height) = self.engine.size();
+                        let cell = (column * cell_width, row * cell_height);
+                        if cell.0 < width && cell.1 < height {
+                            click(
+                                self.engine.as_mut(),
+                                cell,
+                                button,
+                                mods,
+                                &mut self.rng,
+                                &self.brush.pattern,
+                            );
+                        }
+                    }
+                    Input::Key(_) => continue,
+                }
+                should_redraw = true;
+            }
+        }
+    }
+
+    // Moves to a board of the new size, keeping the cells that still fit
+    fn resize(&mut self, columns: usize, rows: usize) {
+        let (old_width, old_height) = self.engine.size();
+        let (width, height) = board_size(columns, rows, self.glyphs);
+        self.columns = columns;
+        self.rows = rows;
+
+        let engine = match self.kind.new_cpu(width, height, &self.rule) {
+            Ok(engine) => engine,
+            Err(e) => {
+                self.message = e.to_string();
+                return;
+            }
+        };
+
+        let state = self.engine.read_state();
+        let (keep_width, keep_height) = (old_width.min(width), old_height.min(height));
+        let kept = state
+            .chunks(old_width)
+            .take(keep_height)
+            .flat_map(|row| &row[..keep_width])
+            .copied()
+            .collect::<Vec<u8>>();
+
+        self.engine = engine;
+        self.engine
+            .set_cells(Region::new(0, 0, keep_width, keep_height), &kept);
+    }
+
+    fn draw(&mut self) {
+        let (width, _) = self.engine.size();
+        let state = self.engine.read_state();
+        // States left over from the last rule are drawn dead until they die
+        let color = |state: u8| *self.palette.get(state as usize).unwrap_or(&self.palette[0]);
+
+        let mut frame = "\x1b[H".to_string();
+        // Colors are only sent when they change
+        let mut colors = None;
+
+        print_blocks(
+            &mut frame,
+            &state,
+            width,
+            self.glyphs.cell_size(),
+            (self.columns, self.rows - 1),
+            |frame, cells| {
+                let (glyph, fg, bg) = match self.glyphs {
+                    Glyphs::Half => ('▀', color(cells[0]), color(cells[1])),
+                    Glyphs::Braille => {
+                        let mut dots = 0;
+                        // Shown in the color of the most alive cell
+                        let mut brightest = None;
+
+                        for (&state, dot) in cells.iter().zip(BRAILLE_DOTS.iter().flatten()) {
+                            if state != 0 {
+                                dots |= dot;
+                                brightest = Some(brightest.map_or(state, |b: u8| b.min(state)));
+                            }
+                        }
+
+                        let fg = color(brightest.unwrap_or(1));
+                        (char::from_u32(0x2800 + dots).unwrap(), fg, self.palette[0])
+                    }
+                };
+
+                if colors != Some((fg, bg)) {
+                    write!(
+                        frame,
+                        "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                        fg.0, fg.1, fg.2, bg.0, bg.1, bg.2
+                    )
+                    .unwrap();
+                    colors = Some((fg, bg));
+                }
+                frame.push(glyph);
+            },
+        );
+
+        let status = format!(
+            " {}  population {}{}{}  {}",
+            self.rule,
+            self.engine.population(),
+            if self.paused { "  paused" } else { "" },
+            if self.message.is_empty() {
+                String::new()
+            } else {
+                format!("  {}", self.message)
+            },
+            HELP
+        );
+        write!(
+            frame,
+            "\x1b[{};1H\x1b[0m\x1b[7m{:<width$.width$}\x1b[0m",
+            self.rows,
+            status,
+            width = self.columns
+        )
+        .unwrap();
+
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(frame.as_bytes()).unwrap();
+        stdout.flush().unwrap();
+    }
+}
+
+// Size of the board that fills a terminal, leaving the bottom line for the
+// status
+fn board_size(columns: usize, rows: usize, glyphs: Glyphs) -> (usize, usize) {
+    let (cell_width, cell_height) = glyphs.cell_size();
+    (
+        columns * cell_width,
+        rows.saturating_sub(1).max(1) * cell_height,
+    )
+}
+
+// Columns and rows of the terminal on stdout
+fn terminal_size() -> Option<(usize, usize)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+
+    if result < 0 || size.ws_col == 0 || size.ws_row < 2 {
+        None
+    } else {
+        Some((size.ws_col as usize, size.ws_row as usize))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Input {
+    Key(u8),
+    // Column and row of a mouse press, from the top left corner
+    Click(usize, usize, MouseButton, Modifiers),
+    Quit,
+}
+
+// Puts the terminal in raw mode on the alternate screen with mouse reporting,
+// and puts everything back when dropped
+struct RawTerminal {
+    original: libc::termios,
+    input: InputBuffer,
+}
+
+impl RawTerminal {
+    fn new() -> Result<Self, &'static str> {
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+            return Err("The terminal output needs a terminal to read keys from");
+        }
+
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } < 0 {
+            return Err("Couldn't get the terminal attributes");
+        }
+
+        let mut raw = original;
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+        }
+        // Keep turning \n into \r\n for messages printed while running
+        raw.c_oflag |= libc::OPOST | libc::ONLCR;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } < 0 {
+            return Err("Couldn't put the terminal in raw mode");
+        }
+
+        // Alternate screen, hidden cursor, SGR mouse presses
+        print!("\x1b[?1049h\x1b[?25l\x1b[?1000h\x1b[?1006h");
+        Ok(RawTerminal {
+            original,
+            input: InputBuffer::default(),
+        })
+    }
+
+    // Waits up to timeout for input, and returns everything that came in
+    fn read(&mut self, timeout: Duration) -> Vec<Input> {
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) } <= 0 {
+            return Vec::new();
+        }
+
+        let mut buffer = [0u8; 256];
+        let len = match std::io::stdin().lock().read(&mut buffer) {
+            Ok(len) => len,
+            Err(_) => return Vec::new(),
+        };
+
+        self.input.feed(&buffer[..len])
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[?1006l\x1b[?1000l\x1b[0m\x1b[?25h\x1b[?1049l");
+        std::io::stdout().flush().unwrap();
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+// Bytes of an escape sequence that was cut off at the end of a read, put in
+// front of the next one
+#[derive(Default)]
+struct InputBuffer {
+    pending: Vec<u8>,
+}
+
+impl InputBuffer {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<Input> {
+        self.pending.extend_from_slice(bytes);
+        let (inputs, used) = parse_input(&self.pending);
+        self.pending.drain(..used);
+
+        // Not the start of anything that ends
+        if self.pending.len() > 64 {
+            self.pending.clear();
+        }
+        inputs
+    }
+}
+
+// Inputs in the bytes, and how many of the bytes they took. An escape sequence
+// cut off at the end is left for the next read, but an escape with nothing
+// after it is the key itself, terminals send sequences in one write
+fn parse_input(all: &[u8]) -> (Vec<Input>, usize) {
+    let mut inputs = Vec::new();
+    let mut bytes = all;
+
+    while let Some((&byte, rest)) = bytes.split_first() {
+        let start = all.len() - bytes.len();
+        bytes = rest;
+
+        match byte {
+            // Ctrl+C doesn't send SIGINT in raw mode
+            b'q' | 0x03 => inputs.push(Input::Quit),
+            0x1b => match rest {
+                // SGR mouse report, \x1b[<button;column;row followed by M
+                // for presses and m for releases
+                [b'[', b'<', report @ ..] => {
+                    match report.iter().position(|&b| b == b'M' || b == b'm') {
+                        Some(end) => {
+                            if report[end] == b'M' {
+                                inputs.extend(parse_click(&report[..end]));
+                            }
+                            bytes = &report[end + 1..];
+                        }
+                        None if report.iter().all(|&b| b.is_ascii_digit() || b == b';') => {
+                            return (inputs, start);
+                        }
+                        None => bytes = &[],
+                    }
+                }
+                // Other escape sequences, like arrow keys, end in a byte
+                // from @ to ~
+                [b'[', ..] | [b'O', ..] => {
+                    match rest[1..].iter().position(|b| (0x40..=0x7e).contains(b)) {
+                        Some(end) => bytes = &rest[end + 2..],
+                        None => return (inputs, start),
+                    }
+                }
+                // Escape on its own
+                [] => inputs.push(Input::Quit),
+                // Alt with a key
+                _ => {}
+            },
+            _ => inputs.push(Input::Key(byte)),
+        }
+    }
+
+    (inputs, all.len())
+}
+
+fn parse_click(report: &[u8]) -> Option<Input> {
+    let report = std::str::from_utf8(report).ok()?;
+    let mut numbers = report.split(';').map(|n| n.parse::<usize>().ok());
+    let (code, column, row) = (numbers.next()??, numbers.next()??, numbers.next()??);
+
+    // Drags and the wheel have bits above the modifiers set
+    if code >= 32 {
+        return None;
+    }
+
+    let button = match code & 3 {
+        0 => MouseButton::Button1,
+        1 => MouseButton::Button3,
+        2 => MouseButton::Button2,
+        _ => return None,
+    };
+
+    let mut mods = Modifiers::empty();
+    if code & 4 != 0 {
+        mods |= Modifiers::Shift;
+    }
+    if code & 16 != 0 {
+        mods |= Modifiers::Control;
+    }
+
+    Some(Input::Click(
+        column.checked_sub(1)?,
+        row.checked_sub(1)?,
+        button,
+        mods,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clicks() {
+        let mut input = InputBuffer::default();
+        assert_eq!(
+            input.feed(b"\x1b[<0;12;5M\x1b[<0;12;5m"),
+            [Input::Click(
+                11,
+                4,
+                MouseButton::Button1,
+                Modifiers::empty()
+            )]
+        );
+        assert_eq!(
+            input.feed(b"\x1b[<18;1;1M"),
+            [Input::Click(0, 0, MouseButton::Button2, Modifiers::Control)]
+        );
+        // Drags and the wheel
+        assert_eq!(input.feed(b"\x1b[<32;3;3M\x1b[<64;3;3M"), []);
+    }
+
+    #[test]
+    fn split_click() {
+        let mut input = InputBuffer::default();
+        assert_eq!(input.feed(b"x\x1b[<0;1"), [Input::Key(b'x')]);
+        assert_eq!(
+            input.feed(b"2;5M"),
+            [Input::Click(
+                11,
+                4,
+                MouseButton::Button1,
+                Modifiers::empty()
+            )]
+        );
+
+        assert_eq!(input.feed(b"\x1b"), [Input::Quit]);
+        assert_eq!(input.feed(b"\x1b["), []);
+        assert_eq!(
+            input.feed(b"<2;1;2M"),
+            [Input::Click(0, 1, MouseButton::Button2, Modifiers::empty())]
+        );
+    }
+
+    #[test]
+    fn arrow_keys() {
+        let mut input = InputBuffer::default();
+        assert_eq!(input.feed(b"\x1b[A\x1b[B\x1bOC\x1b[1;5D"), []);
+        assert_eq!(input.feed(b"\x1b[1;"), []);
+        assert_eq!(input.feed(b"5Af"), [Input::Key(b'f')]);
+    }
+
+    #[test]
+    fn quitting() {
+        let mut input = InputBuffer::default();
+        assert_eq!(input.feed(b"\x1b"), [Input::Quit]);
+        assert_eq!(input.feed(b"\x03"), [Input::Quit]);
+        assert_eq!(input.feed(b"q"), [Input::Quit]);
+        // Alt with a key is the key
+        assert_eq!(input.feed(b"\x1bf"), [Input::Key(b'f')]);
+    }
+
+    #[test]
+    fn keys() {
+        let mut input = InputBuffer::default();
+        assert_eq!(
+            input.feed(b"\t f"),
+            [Input::Key(b'\t'), Input::Key(b' '), Input::Key(b'f')]
+        );
+    }
+}
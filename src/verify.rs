@@ -0,0 +1,252 @@
+use gumdrop::Options;
+
+use crate::game_of_life::BasicGoL;
+use crate::library;
+use crate::rule::{Rule, Topology};
+use crate::simulator::{Region, Simulator};
+use crate::{offscreen_context, GpuGoL, Size};
+
+// One rule of every family the shaders handle, checked on every topology
+// unless rules are given
+const RULES: &[&str] = &[
+    "B3/S23",
+    "B03/S23",
+    "B2n3/S23-q",
+    "B2/S34H",
+    "B13/S012V",
+    "B2/S/C3",
+    "B2/S34/C4H",
+    "R5,C0,M1,S34..58,B34..45,NM",
+    "R3,C4,M0,S2..5,B3..4,NN",
+    "R4,C0,M1,S30..50,B30..40,NC",
+];
+
+const TOPOLOGIES: [Topology; 4] = [
+    Topology::Torus,
+    Topology::Plane,
+    Topology::KleinBottle,
+    Topology::CrossSurface,
+];
+
+// Patterns alone on a board much bigger than them, a library one or else a
+// patch of random cells, so that the GPU skips most tiles. Checked against
+// drawing every tile. Bugs reach further than a tile, and the mask of changed
+// tiles the GPU reads a tick late has to wake tiles further still
+const IDLE: &[(Option<&str>, &str)] = &[
+    (Some("gosper-gun"), "B3/S23:P"),
+    (Some("puffer-train"), "B3/S23"),
+    (None, "R5,C0,M1,S34..58,B34..45,NM"),
+    (None, "R10,C0,M1,S123..212,B123..170,NM:P"),
+];
+const IDLE_SIZE: (usize, usize) = (512, 384);
+
+#[derive(Debug, Options)]
+pub struct VerifyOptions {
+    #[options(help = "print help message")]
+    help: bool,
+
+    #[options(
+        help = "Rule to check, can be given more than once. Defaults to one rule of every family on every topology",
+        parse(try_from_str),
+        no_short
+    )]
+    rule: Vec<Rule>,
+
+    #[options(help = "Generations to run every rule for", default = "200", no_short)]
+    gens: u64,
+
+    #[options(
+        help = "Board size, odd sizes catch more edge cases",
+        default = "123x77",
+        parse(try_from_str),
+        no_short
+    )]
+    size: Size,
+
+    #[options(help = "Chance of a cell starting alive", default = "0.4", no_short)]
+    density: f64,
+
+    #[options(help = "Seed of the random boards", default = "1", no_short)]
+    seed: u64,
+}
+
+// Where two runs first disagree
+struct Divergence {
+    generation: u64,
+    x: usize,
+    y: usize,
+    first: u8,
+    second: u8,
+    cells: usize,
+}
+
+// Runs the gol shaders and BasicGoL side by side from the same random boards,
+// comparing the whole board after every generation. Returns whether all rules
+// matched
+pub fn verify(opts: VerifyOptions) -> bool {
+    let idle = opts.rule.is_empty();
+    let rules = if opts.rule.is_empty() {
+        RULES
+            .iter()
+            .flat_map(|rule| {
+                let rule: Rule = rule.parse().unwrap();
+                TOPOLOGIES
+                    .iter()
+                    .map(move |&topology| Rule { topology, ..rule })
+            })
+            .collect()
+    } else {
+        opts.rule.clone()
+    };
+
+    let Size { width, height } = opts.size;
+    // Big enough for every board, smaller ones draw in its corner
+    let (_glfw, _window) = offscreen_context(
+        width.max(IDLE_SIZE.0) as u32,
+        height.max(IDLE_SIZE.1) as u32,
+    )
+    .unwrap_or_else(|e| panic!("Couldn't verify the shaders: {}", e));
+
+    let mut failures = 0;
+    for rule in &rules {
+        let name = format!("{} on a {:?}", rule, rule.topology);
+
+        let mut gpu = GpuGoL::new(width as u32, height as u32, rule);
+        let mut cpu = BasicGoL::new(width, height, rule);
+        if let Err(e) = gpu.set_rule(rule).and_then(|_| cpu.set_rule(rule)) {
+            println!("{:<48} skipped, {}", name, e);
+            continue;
+        }
+
+        gpu.randomize(opts.density, opts.seed);
+        cpu.randomize(opts.density, opts.seed);
+
+        match compare(&mut gpu, &mut cpu, opts.gens) {
+            None => println!("{:<48} ok", name),
+            Some(d) => {
+                failures += 1;
+                println!(
+                    "{:<48} diverged at generation {}, cell ({}, {}) is {} on the GPU and {} on the CPU, {} cells differ",
+                    name, d.generation, d.x, d.y, d.first, d.second, d.cells
+                );
+            }
+        }
+    }
+
+    println!(
+        "{} of {} rules matched for {} generations",
+        rules.len() - failures,
+        rules.len(),
+        opts.gens
+    );
+    if idle {
+        failures += verify_idle(&opts);
+    }
+    failures == 0
+}
+
+// Runs the IDLE patterns with and without skipping tiles, returns how many
+// didn't match
+fn verify_idle(opts: &VerifyOptions) -> usize {
+    let (width, height) = IDLE_SIZE;
+    let mut failures = 0;
+
+    for &(pattern, rule) in IDLE {
+        let rule: Rule = rule.parse().unwrap();
+        let (name, cells, size) = match pattern {
+            Some(name) => {
+                let pattern = library::find(name).unwrap().pattern();
+                (name, pattern.cells, (pattern.width, pattern.height))
+            }
+            None => {
+                let mut patch = BasicGoL::new(48, 48, &rule);
+                patch.randomize(opts.density, opts.seed);
+                ("a random patch", patch.read_state(), (48, 48))
+            }
+        };
+        let name = format!("{} in {}, skipping tiles", rule, name);
+
+        // Top left, so that what comes out of it has room to go
+        let region = Region::new(width / 8, height / 8, size.0, size.1);
+
+        // GpuGoLs share texture units, so the one drawing every tile runs
+        // first and the other is checked against what it saved
+        let mut every_tile = GpuGoL::new(width as u32, height as u32, &rule);
+        every_tile.draw_every_tile();
+        every_tile.set_cells(region, &cells);
+        let mut states = vec![every_tile.read_state()];
+        for _ in 0..opts.gens {
+            every_tile.tick(1);
+            states.push(every_tile.read_state());
+        }
+        drop(every_tile);
+
+        let mut skipping = GpuGoL::new(width as u32, height as u32, &rule);
+        skipping.set_cells(region, &cells);
+        let divergence = states.iter().zip(0..).find_map(|(state, generation)| {
+            if generation > 0 {
+                skipping.tick(1);
+            }
+            differ(generation, &skipping.read_state(), state, width)
+        });
+
+        match divergence {
+            None => println!("{:<48} ok", name),
+            Some(d) => {
+                failures += 1;
+                println!(
+                    "{:<48} diverged at generation {}, cell ({}, {}) is {} skipping tiles and {} drawing all of them, {} cells differ",
+                    name, d.generation, d.x, d.y, d.first, d.second, d.cells
+                );
+            }
+        }
+    }
+
+    println!(
+        "{} of {} patterns matched drawing every tile for {} generations",
+        IDLE.len() - failures,
+        IDLE.len(),
+        opts.gens
+    );
+    failures
+}
+
+fn compare(
+    first: &mut dyn Simulator,
+    second: &mut dyn Simulator,
+    generations: u64,
+) -> Option<Divergence> {
+    let (width, _) = first.size();
+
+    for generation in 0..=generations {
+        if generation > 0 {
+            first.tick(1);
+            second.tick(1);
+        }
+
+        let divergence = differ(generation, &first.read_state(), &second.read_state(), width);
+        if divergence.is_some() {
+            return divergence;
+        }
+    }
+
+    None
+}
+
+fn differ(generation: u64, first: &[u8], second: &[u8], width: usize) -> Option<Divergence> {
+    let mut differ = first
+        .iter()
+        .zip(second)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b);
+
+    let (i, (&first, &second)) = differ.next()?;
+    Some(Divergence {
+        generation,
+        x: i % width,
+        y: i / width,
+        first,
+        second,
+        cells: differ.count() + 1,
+    })
+}